@@ -1,46 +1,57 @@
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
 use std::fs;
-use std::iter::FromIterator;
+
+const REQUIRED_FIELDS: [&str; 7] = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"];
 
 lazy_static! {
-    static ref HEIGHT_RE: Regex = Regex::new(r"(\d+)(in|cm)").unwrap();
-    static ref HAIR_COLOR_RE: Regex = Regex::new(r"\#[a-z0-9]{6}").unwrap();
-    static ref PID_RE: Regex = Regex::new(r"[0-9]{9}").unwrap();
-    static ref EYE_COLOR: HashSet<&'static str> = HashSet::from_iter(
-        vec!["amb", "blu", "brn", "gry", "grn", "hzl", "oth"]
-            .iter()
-            .cloned()
-    );
+    static ref HEIGHT_RE: Regex = Regex::new(r"^(\d+)(in|cm)$").unwrap();
+    static ref HAIR_COLOR_RE: Regex = Regex::new(r"^#[0-9a-f]{6}$").unwrap();
+    static ref PID_RE: Regex = Regex::new(r"^[0-9]{9}$").unwrap();
 }
 
+#[derive(Debug)]
+struct StringErr(String);
+
 fn main() {
     let data = fs::read_to_string("inputs/day_04.txt").expect("Unable to read file");
 
+    // `day_04 export [csv|jsonl|batch]` writes the normalized passports to stdout
+    if env::args().nth(1).as_deref() == Some("export") {
+        let format = env::args().nth(2).unwrap_or_else(|| "csv".to_owned());
+        let records = parse_records(parse_input(&data));
+        match export(&records, &format) {
+            Ok(output) => print!("{}", output),
+            Err(StringErr(e)) => eprintln!("{}", e),
+        }
+        return;
+    }
+
     println!("Part 1: {}", part_1(parse_input(&data)));
     println!("Part 2: {}", part_2(parse_input(&data)));
 }
 
 fn parse_input(data: &str) -> Vec<&str> {
-    return data.split("\n\n").collect();
+    data.split("\n\n")
+        .filter(|p| !p.trim().is_empty())
+        .collect()
 }
 
-fn get_tokenized_input(passport: &str) -> HashMap<&str, &str> {
-    let mut input: HashMap<&str, &str> = HashMap::new();
-
+fn get_fields(passport: &str) -> Vec<(&str, &str)> {
     passport
-        .lines()
-        .map(|l| l.trim())
-        .into_iter()
-        .for_each(|l| {
-            l.split(" ").into_iter().for_each(|item| {
-                let kv: Vec<&str> = item.split(":").collect();
-                input.insert(kv[0], kv[1]);
-            })
-        });
+        .split_whitespace()
+        .map(|item| match item.find(':') {
+            Some(idx) => (&item[..idx], &item[idx + 1..]),
+            None => (item, ""),
+        })
+        .collect()
+}
 
-    input
+fn get_tokenized_input(passport: &str) -> HashMap<&str, &str> {
+    get_fields(passport).into_iter().collect()
 }
 
 /*
@@ -92,27 +103,13 @@ According to the above rules, your improved system would report 2 valid passport
 Count the number of valid passports - those that have all required fields. Treat cid as optional. In your batch file, how many passports are valid?
  */
 fn part_1(inputs: Vec<&str>) -> i32 {
-    inputs
-        .iter()
-        .filter(|input| has_valid_keys(input))
-        .collect::<Vec<&&str>>()
-        .len() as i32
+    inputs.iter().filter(|input| has_valid_keys(input)).count() as i32
 }
 
 fn has_valid_keys(passport: &str) -> bool {
     let keys = get_tokenized_input(passport);
 
-    if keys.len() < 7 {
-        return false;
-    }
-
-    for key in vec!["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"] {
-        if !keys.contains_key(key) {
-            return false;
-        }
-    }
-
-    return true;
+    REQUIRED_FIELDS.iter().all(|key| keys.contains_key(key))
 }
 
 /*
@@ -187,105 +184,370 @@ iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719
 Count the number of valid passports - those that have all required fields and valid values. Continue to treat cid as optional. In your batch file, how many passports are valid?
  */
 fn part_2(inputs: Vec<&str>) -> i32 {
-    inputs
+    parse_records(inputs)
         .iter()
-        .filter(|input| passport_has_valid_contents(input))
-        .collect::<Vec<&&str>>()
-        .len() as i32
+        .filter(|record| record.is_valid())
+        .count() as i32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Height {
+    unit: String,
+    value: i32,
 }
 
-fn passport_has_valid_contents(passport: &str) -> bool {
-    let kvs = get_tokenized_input(passport);
+fn get_height(line: &str) -> Option<Height> {
+    let results = HEIGHT_RE.captures(line)?;
+
+    let value = results.get(1)?.as_str().parse::<i32>().ok()?;
+    let unit = results.get(2)?.as_str().parse().ok()?;
+
+    Some(Height { value, unit })
+}
 
-    // it would be better to parse this into a struct and validate
-    // but its not worth the time and extra complexity for something this simple
-    for key in vec!["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid"] {
-        if !kvs.contains_key(key) {
-            return false;
+impl Height {
+    fn is_valid_height(&self) -> bool {
+        match self.unit.as_str() {
+            "cm" => (150..=193).contains(&self.value),
+            "in" => (59..=76).contains(&self.value),
+            _ => false,
         }
     }
+}
+
+impl fmt::Display for Height {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.unit)
+    }
+}
 
-    for (key, value) in kvs.iter() {
-        if key == &"byr" {
-            let year = value.parse::<i32>().unwrap();
-            if year < 1920 || year > 2002 {
-                return false;
-            }
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct HairColor {
+    red: u8,
+    green: u8,
+    blue: u8,
+}
+
+fn get_hair_color(value: &str) -> Option<HairColor> {
+    if !HAIR_COLOR_RE.is_match(value) {
+        return None;
+    }
+
+    let channel = |idx: usize| u8::from_str_radix(&value[idx..idx + 2], 16).ok();
+    Some(HairColor {
+        red: channel(1)?,
+        green: channel(3)?,
+        blue: channel(5)?,
+    })
+}
+
+impl fmt::Display for HairColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EyeColor {
+    Amber,
+    Blue,
+    Brown,
+    Gray,
+    Green,
+    Hazel,
+    Other,
+}
+
+impl EyeColor {
+    fn parse(value: &str) -> Option<EyeColor> {
+        match value {
+            "amb" => Some(EyeColor::Amber),
+            "blu" => Some(EyeColor::Blue),
+            "brn" => Some(EyeColor::Brown),
+            "gry" => Some(EyeColor::Gray),
+            "grn" => Some(EyeColor::Green),
+            "hzl" => Some(EyeColor::Hazel),
+            "oth" => Some(EyeColor::Other),
+            _ => None,
         }
+    }
 
-        if key == &"iyr" {
-            let year = value.parse::<i32>().unwrap();
-            if year < 2010 || year > 2020 {
-                return false;
-            }
+    fn code(&self) -> &'static str {
+        match self {
+            EyeColor::Amber => "amb",
+            EyeColor::Blue => "blu",
+            EyeColor::Brown => "brn",
+            EyeColor::Gray => "gry",
+            EyeColor::Green => "grn",
+            EyeColor::Hazel => "hzl",
+            EyeColor::Other => "oth",
         }
+    }
+}
+
+// typed fields are None when missing or unparseable, the raw key:value pairs
+// are kept in their original order so the record can be written back as a batch
+#[derive(Debug, Clone, PartialEq)]
+struct PassportRecord {
+    fields: Vec<(String, String)>,
+    birth_year: Option<i32>,
+    issue_year: Option<i32>,
+    expiration_year: Option<i32>,
+    height: Option<Height>,
+    hair_color: Option<HairColor>,
+    eye_color: Option<EyeColor>,
+    passport_id: Option<String>,
+    country_id: Option<String>,
+    rejections: Vec<String>,
+}
+
+fn parse_records(inputs: Vec<&str>) -> Vec<PassportRecord> {
+    inputs
+        .iter()
+        .map(|input| PassportRecord::parse(input))
+        .collect()
+}
+
+fn get_year(
+    kvs: &HashMap<&str, &str>,
+    key: &str,
+    range: (i32, i32),
+    rejections: &mut Vec<String>,
+) -> Option<i32> {
+    let value = kvs.get(key)?;
+    let year = if value.len() == 4 {
+        value.parse::<i32>().ok()
+    } else {
+        None
+    };
+
+    match year {
+        Some(year) if year < range.0 || year > range.1 => rejections.push(format!(
+            "{} out of range {}-{}: {}",
+            key, range.0, range.1, year
+        )),
+        Some(_) => {}
+        None => rejections.push(format!("{} is not a four digit year: {}", key, value)),
+    }
 
-        if key == &"eyr" {
-            let year = value.parse::<i32>().unwrap();
-            if year < 2020 || year > 2030 {
-                return false;
+    year
+}
+
+impl PassportRecord {
+    fn parse(passport: &str) -> PassportRecord {
+        let fields = get_fields(passport);
+        let kvs: HashMap<&str, &str> = fields.iter().cloned().collect();
+        let mut rejections = Vec::new();
+
+        for key in REQUIRED_FIELDS.iter() {
+            if !kvs.contains_key(key) {
+                rejections.push(format!("{} is missing", key));
             }
         }
 
-        if key == &"hgt" {
-            match get_height(value) {
-                Some(height) => {
-                    if !height.is_valid_height() {
-                        return false;
-                    }
+        let birth_year = get_year(&kvs, "byr", (1920, 2002), &mut rejections);
+        let issue_year = get_year(&kvs, "iyr", (2010, 2020), &mut rejections);
+        let expiration_year = get_year(&kvs, "eyr", (2020, 2030), &mut rejections);
+
+        let height = kvs.get("hgt").and_then(|value| {
+            let height = get_height(value);
+            match &height {
+                Some(h) if !h.is_valid_height() => {
+                    rejections.push(format!("hgt out of range: {}", h))
                 }
-                None => return false,
+                Some(_) => {}
+                None => rejections.push(format!("hgt has no cm or in unit: {}", value)),
             }
-        }
+            height
+        });
 
-        if key == &"hcl" {
-            if HAIR_COLOR_RE.captures(value).is_none() {
-                return false;
+        let hair_color = kvs.get("hcl").and_then(|value| {
+            let color = get_hair_color(value);
+            if color.is_none() {
+                rejections.push(format!("hcl is not a hex color: {}", value));
             }
-        }
+            color
+        });
 
-        if key == &"ecl" {
-            if !EYE_COLOR.contains(value) {
-                return false;
+        let eye_color = kvs.get("ecl").and_then(|value| {
+            let color = EyeColor::parse(value);
+            if color.is_none() {
+                rejections.push(format!("ecl is not a known eye color: {}", value));
             }
-        }
+            color
+        });
 
-        if key == &"pid" {
-            if PID_RE.captures(value).is_none() {
-                return false;
+        let passport_id = kvs.get("pid").and_then(|value| {
+            if PID_RE.is_match(value) {
+                return Some(value.to_string());
             }
+            rejections.push(format!("pid is not a nine digit number: {}", value));
+            None
+        });
+
+        PassportRecord {
+            fields: fields
+                .iter()
+                .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+            birth_year,
+            issue_year,
+            expiration_year,
+            height,
+            hair_color,
+            eye_color,
+            passport_id,
+            country_id: kvs.get("cid").map(|v| v.to_string()),
+            rejections,
         }
     }
 
-    return true;
+    fn is_valid(&self) -> bool {
+        self.rejections.is_empty()
+    }
+
+    fn to_batch(&self) -> String {
+        self.fields
+            .iter()
+            .map(|(k, v)| format!("{}:{}", k, v))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn to_csv(&self) -> String {
+        let columns = vec![
+            optional(&self.birth_year),
+            optional(&self.issue_year),
+            optional(&self.expiration_year),
+            optional(&self.height),
+            optional(&self.hair_color),
+            self.eye_color
+                .map(|e| e.code().to_owned())
+                .unwrap_or_default(),
+            self.passport_id.clone().unwrap_or_default(),
+            self.country_id.clone().unwrap_or_default(),
+            self.is_valid().to_string(),
+            self.rejections.join("; "),
+        ];
+
+        columns
+            .iter()
+            .map(|c| csv_escape(c))
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    fn to_json(&self) -> String {
+        let height = match &self.height {
+            Some(h) => format!(
+                "{{\"value\":{},\"unit\":{}}}",
+                h.value,
+                json_string(&h.unit)
+            ),
+            None => "null".to_owned(),
+        };
+
+        let fields = vec![
+            ("byr", json_optional(&self.birth_year)),
+            ("iyr", json_optional(&self.issue_year)),
+            ("eyr", json_optional(&self.expiration_year)),
+            ("hgt", height),
+            (
+                "hcl",
+                json_optional_string(&self.hair_color.map(|c| c.to_string())),
+            ),
+            (
+                "ecl",
+                json_optional_string(&self.eye_color.map(|e| e.code().to_owned())),
+            ),
+            ("pid", json_optional_string(&self.passport_id)),
+            ("cid", json_optional_string(&self.country_id)),
+            ("valid", self.is_valid().to_string()),
+            (
+                "reasons",
+                format!(
+                    "[{}]",
+                    self.rejections
+                        .iter()
+                        .map(|r| json_string(r))
+                        .collect::<Vec<String>>()
+                        .join(",")
+                ),
+            ),
+        ];
+
+        format!(
+            "{{{}}}",
+            fields
+                .iter()
+                .map(|(k, v)| format!("\"{}\":{}", k, v))
+                .collect::<Vec<String>>()
+                .join(",")
+        )
+    }
 }
 
-struct Height {
-    unit: String,
-    value: i32,
+const CSV_HEADER: &str = "byr,iyr,eyr,hgt,hcl,ecl,pid,cid,valid,reasons";
+
+fn export(records: &[PassportRecord], format: &str) -> Result<String, StringErr> {
+    let lines: Vec<String> = match format {
+        "csv" => std::iter::once(CSV_HEADER.to_owned())
+            .chain(records.iter().map(|r| r.to_csv()))
+            .collect(),
+        "jsonl" => records.iter().map(|r| r.to_json()).collect(),
+        "batch" => {
+            return Ok(records
+                .iter()
+                .map(|r| r.to_batch())
+                .collect::<Vec<String>>()
+                .join("\n\n")
+                + "\n")
+        }
+        _ => return Err(StringErr(format!("unknown export format: {}", format))),
+    };
+
+    Ok(lines.iter().map(|l| format!("{}\n", l)).collect())
 }
 
-fn get_height(line: &str) -> Option<Height> {
-    let results = HEIGHT_RE.captures(line)?;
+fn optional<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(|v| v.to_string()).unwrap_or_default()
+}
 
-    let value = results.get(1)?.as_str().parse::<i32>().ok()?;
-    let unit = results.get(2)?.as_str().parse().ok()?;
+fn csv_escape(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
 
-    Some(Height { value, unit })
+fn json_optional<T: ToString>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_owned())
 }
 
-impl Height {
-    fn is_valid_height(&self) -> bool {
-        if self.unit == "cm" {
-            return self.value >= 150 && self.value <= 193;
-        }
+fn json_optional_string(value: &Option<String>) -> String {
+    value
+        .as_ref()
+        .map(|v| json_string(v))
+        .unwrap_or_else(|| "null".to_owned())
+}
 
-        if self.unit == "in" {
-            return self.value >= 59 && self.value <= 76;
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
         }
-
-        return false;
     }
+    out.push('"');
+    out
 }
 
 #[cfg(test)]
@@ -346,4 +608,89 @@ iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719",
 
         assert_eq!(part_2(parse_input(&data)), 4)
     }
+
+    #[test]
+    fn test_record_parsing() {
+        let record = PassportRecord::parse(
+            "hcl:#623a2f pid:087499704 hgt:74in\necl:grn iyr:2012 eyr:2030 byr:1980",
+        );
+
+        assert_eq!(record.birth_year, Some(1980));
+        assert_eq!(
+            record.height,
+            Some(Height {
+                value: 74,
+                unit: "in".to_owned()
+            })
+        );
+        assert_eq!(
+            record.hair_color,
+            Some(HairColor {
+                red: 0x62,
+                green: 0x3a,
+                blue: 0x2f
+            })
+        );
+        assert_eq!(record.eye_color, Some(EyeColor::Green));
+        assert_eq!(record.country_id, None);
+        assert!(record.is_valid());
+    }
+
+    #[test]
+    fn test_record_rejections() {
+        let record = PassportRecord::parse(
+            "hgt:59cm ecl:zzz\neyr:2038 hcl:74454a iyr:2023\npid:3556412378 byr:2007",
+        );
+
+        assert!(!record.is_valid());
+        assert!(PassportRecord::parse("pid:0123456789")
+            .rejections
+            .contains(&"pid is not a nine digit number: 0123456789".to_owned()));
+        assert_eq!(
+            record.rejections,
+            vec![
+                "byr out of range 1920-2002: 2007",
+                "iyr out of range 2010-2020: 2023",
+                "eyr out of range 2020-2030: 2038",
+                "hgt out of range: 59cm",
+                "hcl is not a hex color: 74454a",
+                "ecl is not a known eye color: zzz",
+                "pid is not a nine digit number: 3556412378",
+            ]
+        );
+        assert_eq!(
+            PassportRecord::parse("iyr:2013 ecl:amb").rejections[..2],
+            ["byr is missing".to_owned(), "eyr is missing".to_owned()]
+        );
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let records = parse_records(parse_input(
+            "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd\nbyr:1937 iyr:2017 cid:147 hgt:183cm\n\niyr:2019\nhcl:#602927 eyr:1967 hgt:170cm\necl:grn pid:012533040 byr:1946\n",
+        ));
+
+        assert_eq!(
+            export(&records, "csv").unwrap(),
+            "byr,iyr,eyr,hgt,hcl,ecl,pid,cid,valid,reasons
+1937,2017,2020,183cm,#fffffd,gry,860033327,147,true,
+1946,2019,1967,170cm,#602927,grn,012533040,,false,eyr out of range 2020-2030: 1967
+"
+        );
+        assert_eq!(
+            export(&records, "jsonl").unwrap().lines().next().unwrap(),
+            "{\"byr\":1937,\"iyr\":2017,\"eyr\":2020,\"hgt\":{\"value\":183,\"unit\":\"cm\"},\"hcl\":\"#fffffd\",\"ecl\":\"gry\",\"pid\":\"860033327\",\"cid\":\"147\",\"valid\":true,\"reasons\":[]}"
+        );
+        assert!(export(&records, "xml").is_err());
+    }
+
+    #[test]
+    fn test_batch_round_trip() {
+        let records = parse_records(parse_input(
+            "eyr:1972 cid:100\nhcl:#18171d ecl:amb hgt:170 pid:186cm iyr:2018 byr:1926\n\nhcl:#888785\nhgt:164cm byr:2001 iyr:2015 cid:88\npid:545766238 ecl:hzl\neyr:2022",
+        ));
+
+        let batch = export(&records, "batch").unwrap();
+        assert_eq!(parse_records(parse_input(&batch)), records);
+    }
 }
@@ -1,12 +1,26 @@
-use std::collections::HashSet;
+use std::env;
 use std::fs;
-use std::iter::FromIterator;
+use std::ops::Range;
+
+#[derive(Debug)]
+struct StringErr(String);
 
 fn main() {
+    // `day_05 decode <code> [rows columns]`, `day_05 encode <row> <column> [rows columns]`
+    // and `day_05 map [rows columns]`
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        match run_command(&args) {
+            Ok(output) => println!("{}", output),
+            Err(StringErr(e)) => eprintln!("{}", e),
+        }
+        return;
+    }
+
     let data = fs::read_to_string("inputs/day_05.txt").expect("Unable to read file");
 
-    println!("Part 1: {}", part_1(parse_input(&data)));
-    println!("Part 2: {}", part_2(parse_input(&data)));
+    println!("Part 1: {}", part_1(parse_input(&data)).unwrap());
+    println!("Part 2: {}", part_2(parse_input(&data)).unwrap());
 }
 
 fn parse_input(input: &str) -> Vec<&str> {
@@ -17,6 +31,157 @@ fn parse_input(input: &str) -> Vec<&str> {
         .collect()
 }
 
+fn run_command(args: &[String]) -> Result<String, StringErr> {
+    let number = |idx: usize| -> Result<u32, StringErr> {
+        let arg = args
+            .get(idx)
+            .ok_or_else(|| StringErr(format!("missing argument {}", idx)))?;
+        arg.parse::<u32>()
+            .map_err(|_| StringErr(format!("{} is not a number", arg)))
+    };
+    let geometry = |idx: usize| -> Result<PlaneGeometry, StringErr> {
+        if args.len() <= idx {
+            return Ok(DEFAULT_GEOMETRY);
+        }
+        PlaneGeometry::new(number(idx)?, number(idx + 1)?)
+    };
+
+    match args[0].as_str() {
+        "decode" => {
+            let code = args
+                .get(1)
+                .ok_or_else(|| StringErr("missing boarding pass".to_owned()))?;
+            let pass = BoardingPass::decode(code, &geometry(2)?)?;
+            Ok(format!(
+                "row {}, column {}, seat ID {}",
+                pass.row, pass.column, pass.id
+            ))
+        }
+        "encode" => {
+            let geometry = geometry(3)?;
+            Ok(BoardingPass::new(number(1)?, number(2)?, &geometry)?.encode(&geometry))
+        }
+        "map" => {
+            let geometry = geometry(1)?;
+            let data = fs::read_to_string("inputs/day_05.txt")
+                .map_err(|e| StringErr(format!("unable to read inputs/day_05.txt: {}", e)))?;
+            let passes = get_passes(parse_input(&data), &geometry)?;
+            Ok(SeatMap::new(&passes, &geometry).report())
+        }
+        command => Err(StringErr(format!("unknown command: {}", command))),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PlaneGeometry {
+    rows: u32,
+    columns: u32,
+}
+
+const DEFAULT_GEOMETRY: PlaneGeometry = PlaneGeometry {
+    rows: 128,
+    columns: 8,
+};
+
+impl PlaneGeometry {
+    fn new(rows: u32, columns: u32) -> Result<Self, StringErr> {
+        if !rows.is_power_of_two() || !columns.is_power_of_two() {
+            return Err(StringErr(format!(
+                "plane geometry must be powers of two, got {} rows and {} columns",
+                rows, columns
+            )));
+        }
+        // seat ids are row * columns + column, so every seat needs an id that fits
+        if rows.checked_mul(columns).is_none() {
+            return Err(StringErr(format!(
+                "plane geometry too large, {} rows of {} columns",
+                rows, columns
+            )));
+        }
+
+        Ok(Self { rows, columns })
+    }
+
+    fn row_bits(&self) -> usize {
+        self.rows.trailing_zeros() as usize
+    }
+
+    fn column_bits(&self) -> usize {
+        self.columns.trailing_zeros() as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BoardingPass {
+    row: u32,
+    column: u32,
+    id: u32,
+}
+
+impl BoardingPass {
+    fn new(row: u32, column: u32, geometry: &PlaneGeometry) -> Result<Self, StringErr> {
+        if row >= geometry.rows || column >= geometry.columns {
+            return Err(StringErr(format!(
+                "seat ({}, {}) is outside a {}x{} plane",
+                row, column, geometry.rows, geometry.columns
+            )));
+        }
+
+        Ok(Self {
+            row,
+            column,
+            id: row * geometry.columns + column,
+        })
+    }
+
+    // each character is one bit of the seat, F/L are 0 and B/R are 1
+    fn decode(code: &str, geometry: &PlaneGeometry) -> Result<Self, StringErr> {
+        let row_bits = geometry.row_bits();
+        if code.chars().count() != row_bits + geometry.column_bits() {
+            return Err(StringErr(format!(
+                "boarding pass {} should be {} characters long",
+                code,
+                row_bits + geometry.column_bits()
+            )));
+        }
+
+        let mut row = 0;
+        let mut column = 0;
+        for (i, c) in code.chars().enumerate() {
+            match (i < row_bits, c) {
+                (true, 'F') => row <<= 1,
+                (true, 'B') => row = (row << 1) | 1,
+                (false, 'L') => column <<= 1,
+                (false, 'R') => column = (column << 1) | 1,
+                _ => {
+                    return Err(StringErr(format!(
+                        "unexpected {:?} at position {} of boarding pass {}",
+                        c, i, code
+                    )))
+                }
+            }
+        }
+
+        Self::new(row, column, geometry)
+    }
+
+    fn from_id(id: u32, geometry: &PlaneGeometry) -> Result<Self, StringErr> {
+        Self::new(id / geometry.columns, id % geometry.columns, geometry)
+    }
+
+    fn encode(&self, geometry: &PlaneGeometry) -> String {
+        let bits = |value: u32, width: usize, low: char, high: char| {
+            (0..width)
+                .rev()
+                .map(|bit| if value >> bit & 1 == 1 { high } else { low })
+                .collect::<String>()
+        };
+
+        bits(self.row, geometry.row_bits(), 'F', 'B')
+            + &bits(self.column, geometry.column_bits(), 'L', 'R')
+    }
+}
+
 /*
 --- Day 5: Binary Boarding ---
 
@@ -60,8 +225,12 @@ Here are some other boarding passes:
 
 As a sanity check, look through your list of boarding passes. What is the highest seat ID on a boarding pass?
  */
-fn part_1(inputs: Vec<&str>) -> i32 {
-    get_ids(inputs).iter().max().unwrap().clone()
+fn part_1(inputs: Vec<&str>) -> Result<u32, StringErr> {
+    get_passes(inputs, &DEFAULT_GEOMETRY)?
+        .iter()
+        .map(|pass| pass.id)
+        .max()
+        .ok_or_else(|| StringErr("no boarding passes".to_owned()))
 }
 
 /*
@@ -75,46 +244,117 @@ Your seat wasn't at the very front or back, though; the seats with IDs +1 and -1
 
 What is the ID of your seat?
  */
-fn part_2(inputs: Vec<&str>) -> i32 {
-    let found_ids: HashSet<i32> = HashSet::from_iter(get_ids(inputs).iter().cloned());
-
-    let all_possible_ids: HashSet<i32> =
-        (*found_ids.iter().min().unwrap()..*found_ids.iter().max().unwrap()).collect();
-    *all_possible_ids.difference(&found_ids).next().unwrap() as i32
+fn part_2(inputs: Vec<&str>) -> Result<u32, StringErr> {
+    let passes = get_passes(inputs, &DEFAULT_GEOMETRY)?;
+    let free_seats = SeatMap::new(&passes, &DEFAULT_GEOMETRY).free_seats_between_passengers();
+    match free_seats.as_slice() {
+        [seat] => Ok(seat.id),
+        [] => Err(StringErr("no free seat".to_owned())),
+        _ => Err(StringErr(format!(
+            "{} free seats between passengers",
+            free_seats.len()
+        ))),
+    }
 }
 
-fn get_ids(inputs: Vec<&str>) -> Vec<i32> {
-    inputs
-        .iter()
-        .map(|input| {
-            let (row, column) = get_row_value(input.parse().unwrap());
-            return (row * 8) + column;
-        })
-        .collect()
+// '#' is an occupied seat, '.' a free seat and 'o' a free seat with both neighbours occupied
+struct SeatMap {
+    geometry: PlaneGeometry,
+    occupied: Vec<bool>,
 }
 
-fn get_row_value(operation: String) -> (i32, i32) {
-    let mut row_floor = 0;
-    let mut row_limit = 128;
-    let mut column_floor = 0;
-    let mut column_limit = 8;
-
-    operation.chars().for_each(|c| {
-        let new_row = ((row_limit - row_floor) / 2) + row_floor;
-        let new_column = ((column_limit - column_floor) / 2) + column_floor;
-
-        match c {
-            'F' => row_limit = new_row,
-            'B' => row_floor = new_row,
-            'L' => column_limit = new_column,
-            'R' => column_floor = new_column,
-            _ => {
-                panic!("unknown char")
+impl SeatMap {
+    fn new(passes: &[BoardingPass], geometry: &PlaneGeometry) -> Self {
+        // PlaneGeometry::new guarantees rows * columns fits in u32
+        let mut occupied = vec![false; (geometry.rows * geometry.columns) as usize];
+        passes
+            .iter()
+            .for_each(|pass| occupied[pass.id as usize] = true);
+
+        Self {
+            geometry: *geometry,
+            occupied,
+        }
+    }
+
+    fn is_occupied(&self, id: u32) -> bool {
+        self.occupied.get(id as usize).copied().unwrap_or(false)
+    }
+
+    fn is_between_passengers(&self, id: u32) -> bool {
+        id > 0 && !self.is_occupied(id) && self.is_occupied(id - 1) && self.is_occupied(id + 1)
+    }
+
+    fn free_seats_between_passengers(&self) -> Vec<BoardingPass> {
+        (0..self.occupied.len() as u32)
+            .filter(|&id| self.is_between_passengers(id))
+            .map(|id| BoardingPass::from_id(id, &self.geometry).unwrap())
+            .collect()
+    }
+
+    // the free seats before the first and after the last occupied one
+    fn front_gap(&self) -> Range<u32> {
+        let first = self.occupied.iter().position(|&o| o);
+        0..first.unwrap_or(self.occupied.len()) as u32
+    }
+
+    fn back_gap(&self) -> Range<u32> {
+        let last = self.occupied.iter().rposition(|&o| o);
+        last.map(|l| l + 1).unwrap_or(0) as u32..self.occupied.len() as u32
+    }
+
+    fn render(&self) -> String {
+        (0..self.geometry.rows)
+            .map(|row| {
+                let seats = (0..self.geometry.columns)
+                    .map(|column| {
+                        let id = row * self.geometry.columns + column;
+                        if self.is_occupied(id) {
+                            '#'
+                        } else if self.is_between_passengers(id) {
+                            'o'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect::<String>();
+                format!("{:>4} {}\n", row, seats)
+            })
+            .collect()
+    }
+
+    fn report(&self) -> String {
+        let describe_gap = |gap: Range<u32>| {
+            if gap.is_empty() {
+                return "none".to_owned();
             }
+            format!(
+                "seat IDs {}-{} ({} seats)",
+                gap.start,
+                gap.end - 1,
+                gap.end - gap.start
+            )
+        };
+
+        let mut report = self.render();
+        report += &format!("front gap: {}\n", describe_gap(self.front_gap()));
+        report += &format!("back gap: {}\n", describe_gap(self.back_gap()));
+        report += "free seats between passengers:";
+        for seat in self.free_seats_between_passengers() {
+            report += &format!(
+                "\n  seat ID {} (row {}, column {})",
+                seat.id, seat.row, seat.column
+            );
         }
-    });
+        report
+    }
+}
 
-    (row_floor, column_floor)
+fn get_passes(inputs: Vec<&str>, geometry: &PlaneGeometry) -> Result<Vec<BoardingPass>, StringErr> {
+    inputs
+        .iter()
+        .map(|input| BoardingPass::decode(input, geometry))
+        .collect()
 }
 
 #[cfg(test)]
@@ -123,9 +363,81 @@ mod tests {
 
     #[test]
     fn test_get_row_column() {
-        assert_eq!(get_row_value("BFFFBBFRRR".to_string()), (70, 7));
-        assert_eq!(get_row_value("FFFBBBFRRR".to_string()), (14, 7));
-        assert_eq!(get_row_value("BBFFBBFRLL".to_string()), (102, 4));
+        let decode = |code| {
+            let pass = BoardingPass::decode(code, &DEFAULT_GEOMETRY).unwrap();
+            (pass.row, pass.column, pass.id)
+        };
+
+        assert_eq!(decode("FBFBBFFRLR"), (44, 5, 357));
+        assert_eq!(decode("BFFFBBFRRR"), (70, 7, 567));
+        assert_eq!(decode("FFFBBBFRRR"), (14, 7, 119));
+        assert_eq!(decode("BBFFBBFRLL"), (102, 4, 820));
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let geometry = PlaneGeometry::new(32, 16).unwrap();
+        let pass = BoardingPass::new(19, 6, &geometry).unwrap();
+
+        assert_eq!(pass.id, 19 * 16 + 6);
+        assert_eq!(pass.encode(&geometry), "BFFBBLRRL");
+        assert_eq!(BoardingPass::decode("BFFBBLRRL", &geometry).unwrap(), pass);
+        assert_eq!(
+            BoardingPass::new(44, 5, &DEFAULT_GEOMETRY)
+                .unwrap()
+                .encode(&DEFAULT_GEOMETRY),
+            "FBFBBFFRLR"
+        );
+    }
+
+    #[test]
+    fn test_run_command() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<String>>();
+
+        assert_eq!(
+            run_command(&args("decode FBFBBFFRLR")).unwrap(),
+            "row 44, column 5, seat ID 357"
+        );
+        assert_eq!(
+            run_command(&args("encode 19 6 32 16")).unwrap(),
+            "BFFBBLRRL"
+        );
+        assert!(run_command(&args("encode 19 6 30 16")).is_err());
+        assert!(run_command(&args("encode 65535 65535 65536 65536")).is_err());
+    }
+
+    #[test]
+    fn test_invalid_boarding_passes() {
+        assert!(PlaneGeometry::new(100, 8).is_err());
+        assert!(PlaneGeometry::new(65536, 65536).is_err());
+        assert!(BoardingPass::decode("FBFBBFFRL", &DEFAULT_GEOMETRY).is_err());
+        assert!(BoardingPass::decode("FBFBBFRRLR", &DEFAULT_GEOMETRY).is_err());
+        assert!(BoardingPass::decode("FBFBBFFXLR", &DEFAULT_GEOMETRY).is_err());
+        assert!(BoardingPass::new(128, 0, &DEFAULT_GEOMETRY).is_err());
+    }
+
+    #[test]
+    fn test_seat_map() {
+        let geometry = PlaneGeometry::new(4, 4).unwrap();
+        let passes = vec![2, 3, 5, 6, 7, 9, 11]
+            .into_iter()
+            .map(|id| BoardingPass::from_id(id, &geometry).unwrap())
+            .collect::<Vec<BoardingPass>>();
+        let map = SeatMap::new(&passes, &geometry);
+
+        assert_eq!(map.render(), "   0 ..##\n   1 o###\n   2 o#o#\n   3 ....\n");
+        assert_eq!(
+            map.free_seats_between_passengers()
+                .iter()
+                .map(|seat| seat.id)
+                .collect::<Vec<u32>>(),
+            vec![4, 8, 10]
+        );
+        assert_eq!(map.front_gap(), 0..2);
+        assert_eq!(map.back_gap(), 12..16);
+        assert!(map
+            .report()
+            .contains("front gap: seat IDs 0-1 (2 seats)\nback gap: seat IDs 12-15 (4 seats)"));
     }
 
     #[test]
@@ -134,6 +446,6 @@ mod tests {
         FFFBBBFRRR
         BBFFBBFRLL";
 
-        assert_eq!(part_1(parse_input(input)), 820);
+        assert_eq!(part_1(parse_input(input)).unwrap(), 820);
     }
 }
@@ -1,12 +1,55 @@
-use std::collections::HashSet;
-use std::fs;
-use std::iter::FromIterator;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+
+#[derive(Debug)]
+struct StringErr(String);
 
 fn main() {
-    let data = fs::read_to_string("inputs/day_06.txt").expect("Unable to read file");
+    let open = || BufReader::new(File::open("inputs/day_06.txt").expect("Unable to read file"));
+
+    // `day_06 query <quorum>`, `day_06 questions <quorum>` and `day_06 histogram` where
+    // quorum is one of anyone, everyone, nobody, at-least K or exactly K
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        match run_command(&args, open()) {
+            Ok(output) => println!("{}", output),
+            Err(StringErr(e)) => eprintln!("{}", e),
+        }
+        return;
+    }
+
+    println!("Part 1: {}", part_1(open()));
+    println!("Part 2: {}", part_2(open()));
+}
 
-    println!("Part 1: {}", part_1(parse_input(&data)));
-    println!("Part 2: {}", part_2(parse_input(&data)));
+fn run_command<R: BufRead>(args: &[String], reader: R) -> Result<String, StringErr> {
+    let groups = GroupReader::new(reader);
+
+    match args[0].as_str() {
+        "query" => {
+            let quorum = Quorum::parse(&args[1..])?;
+            Ok(count_answers(groups, quorum)?.to_string())
+        }
+        "questions" => {
+            let quorum = Quorum::parse(&args[1..])?;
+            groups
+                .enumerate()
+                .map(|(i, g)| {
+                    let questions = g?.questions(quorum).into_iter().collect::<String>();
+                    Ok(format!("group {}: {}", i + 1, questions))
+                })
+                .collect::<Result<Vec<String>, StringErr>>()
+                .map(|lines| lines.join("\n"))
+        }
+        "histogram" => Ok(get_survey_histogram(groups)?
+            .iter()
+            .map(|(question, count)| format!("{}: {}", question, count))
+            .collect::<Vec<String>>()
+            .join("\n")),
+        command => Err(StringErr(format!("unknown command: {}", command))),
+    }
 }
 
 /*
@@ -54,20 +97,8 @@ In this example, the sum of these counts is 3 + 3 + 3 + 1 + 1 = 11.
 
 For each group, count the number of questions to which anyone answered "yes". What is the sum of those counts?
  */
-fn part_1(groups: Vec<&str>) -> i32 {
-    groups
-        .iter()
-        .map(|group| {
-            group.lines().fold(HashSet::new(), |mut acc, x| {
-                x.chars().for_each(|c| {
-                    acc.insert(c);
-                    return;
-                });
-                acc
-            })
-        })
-        .map(|hs| hs.len() as i32)
-        .fold(0, |acc, x| acc + x)
+fn part_1<R: BufRead>(reader: R) -> i32 {
+    count_answers(GroupReader::new(reader), Quorum::Anyone).unwrap() as i32
 }
 
 /*
@@ -107,30 +138,205 @@ In this example, the sum of these counts is 3 + 0 + 1 + 1 + 1 = 6.
 
 For each group, count the number of questions to which everyone answered "yes". What is the sum of those counts?
  */
-fn part_2(groups: Vec<&str>) -> i32 {
-    groups
-        .iter()
-        .map(|group| {
-            let mut lines = group
-                .lines()
-                .map(|x| HashSet::from_iter(x.chars().into_iter()));
-
-            // intersect the sets onto itself
-            lines
-                .next()
-                .map(|set: HashSet<char>| {
-                    lines.fold(set, |set1, set2| {
-                        HashSet::from_iter(set1.intersection(&set2).map(|c| c.to_owned()))
-                    })
-                })
-                .unwrap()
+fn part_2<R: BufRead>(reader: R) -> i32 {
+    count_answers(GroupReader::new(reader), Quorum::Everyone).unwrap() as i32
+}
+
+const QUESTIONS: std::ops::RangeInclusive<char> = 'a'..='z';
+
+// one bit per question, bit 0 is 'a' and bit 25 is 'z'
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct AnswerSet(u32);
+
+const ALL_QUESTIONS: AnswerSet = AnswerSet((1 << 26) - 1);
+
+impl AnswerSet {
+    fn parse(answers: &str) -> Result<AnswerSet, StringErr> {
+        answers.chars().try_fold(AnswerSet::default(), |acc, c| {
+            if !QUESTIONS.contains(&c) {
+                return Err(StringErr(format!(
+                    "unknown question {:?} in {}",
+                    c, answers
+                )));
+            }
+            Ok(AnswerSet(acc.0 | 1 << (c as u32 - 'a' as u32)))
         })
-        .map(|hs| hs.len() as i32)
-        .fold(0, |acc, x| acc + x)
+    }
+
+    fn union(self, other: AnswerSet) -> AnswerSet {
+        AnswerSet(self.0 | other.0)
+    }
+
+    fn intersection(self, other: AnswerSet) -> AnswerSet {
+        AnswerSet(self.0 & other.0)
+    }
+
+    fn contains(self, question: char) -> bool {
+        QUESTIONS.contains(&question) && self.0 >> (question as u32 - 'a' as u32) & 1 == 1
+    }
+
+    fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
 }
 
-fn parse_input(data: &str) -> Vec<&str> {
-    return data.split("\n\n").collect();
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quorum {
+    Anyone,
+    Everyone,
+    Nobody,
+    AtLeast(usize),
+    Exactly(usize),
+}
+
+impl Quorum {
+    fn parse(args: &[String]) -> Result<Quorum, StringErr> {
+        let people = || -> Result<usize, StringErr> {
+            let arg = args
+                .get(1)
+                .ok_or_else(|| StringErr("missing number of people".to_owned()))?;
+            arg.parse::<usize>()
+                .map_err(|_| StringErr(format!("{} is not a number", arg)))
+        };
+
+        match args.first().map(|a| a.as_str()) {
+            Some("anyone") => Ok(Quorum::Anyone),
+            Some("everyone") => Ok(Quorum::Everyone),
+            Some("nobody") => Ok(Quorum::Nobody),
+            Some("at-least") => Ok(Quorum::AtLeast(people()?)),
+            Some("exactly") => Ok(Quorum::Exactly(people()?)),
+            quorum => Err(StringErr(format!("unknown quorum: {:?}", quorum))),
+        }
+    }
+}
+
+// a group is folded person by person so it never holds more than the running
+// union, intersection and per-question counts
+#[derive(Debug, Clone, PartialEq)]
+struct GroupAnswers {
+    people: usize,
+    anyone: AnswerSet,
+    everyone: AnswerSet,
+    counts: [usize; 26],
+}
+
+impl GroupAnswers {
+    fn new() -> GroupAnswers {
+        GroupAnswers {
+            people: 0,
+            anyone: AnswerSet::default(),
+            everyone: ALL_QUESTIONS,
+            counts: [0; 26],
+        }
+    }
+
+    fn add_person(&mut self, answers: AnswerSet) {
+        self.people += 1;
+        self.anyone = self.anyone.union(answers);
+        self.everyone = self.everyone.intersection(answers);
+        for (i, count) in self.counts.iter_mut().enumerate() {
+            *count += (answers.0 >> i & 1) as usize;
+        }
+    }
+
+    // number of people in the group that answered "yes" to each question
+    fn histogram(&self) -> BTreeMap<char, usize> {
+        QUESTIONS.zip(self.counts.iter().cloned()).collect()
+    }
+
+    fn answer_set(&self, quorum: Quorum) -> AnswerSet {
+        match quorum {
+            Quorum::Anyone => self.anyone,
+            Quorum::Everyone => self.everyone,
+            Quorum::Nobody => AnswerSet(ALL_QUESTIONS.0 & !self.anyone.0),
+            Quorum::AtLeast(k) => self.counts_matching(|count| count >= k),
+            Quorum::Exactly(k) => self.counts_matching(|count| count == k),
+        }
+    }
+
+    fn counts_matching(&self, f: impl Fn(usize) -> bool) -> AnswerSet {
+        AnswerSet(
+            self.counts
+                .iter()
+                .enumerate()
+                .filter(|&(_, &count)| f(count))
+                .fold(0, |acc, (i, _)| acc | 1 << i),
+        )
+    }
+
+    fn questions(&self, quorum: Quorum) -> Vec<char> {
+        let answers = self.answer_set(quorum);
+        QUESTIONS.filter(|&q| answers.contains(q)).collect()
+    }
+}
+
+// streams groups out of a survey dump, one line at a time
+struct GroupReader<R: BufRead> {
+    lines: Lines<R>,
+}
+
+impl<R: BufRead> GroupReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for GroupReader<R> {
+    type Item = Result<GroupAnswers, StringErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut group = GroupAnswers::new();
+
+        for line in &mut self.lines {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(StringErr(e.to_string()))),
+            };
+
+            let line = line.trim();
+            if line.is_empty() {
+                if group.people > 0 {
+                    return Some(Ok(group));
+                }
+                continue;
+            }
+
+            match AnswerSet::parse(line) {
+                Ok(answers) => group.add_person(answers),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        if group.people > 0 {
+            Some(Ok(group))
+        } else {
+            None
+        }
+    }
+}
+
+fn count_answers<I>(groups: I, quorum: Quorum) -> Result<usize, StringErr>
+where
+    I: Iterator<Item = Result<GroupAnswers, StringErr>>,
+{
+    groups.map(|g| Ok(g?.answer_set(quorum).len())).sum()
+}
+
+fn get_survey_histogram<I>(mut groups: I) -> Result<BTreeMap<char, usize>, StringErr>
+where
+    I: Iterator<Item = Result<GroupAnswers, StringErr>>,
+{
+    groups.try_fold(
+        QUESTIONS.map(|q| (q, 0)).collect(),
+        |mut acc: BTreeMap<char, usize>, g| {
+            g?.histogram()
+                .into_iter()
+                .for_each(|(q, count)| *acc.entry(q).or_insert(0) += count);
+            Ok(acc)
+        },
+    )
 }
 
 #[cfg(test)]
@@ -155,7 +361,7 @@ a
 
 b";
 
-        assert_eq!(part_1(parse_input(input)), 11);
+        assert_eq!(part_1(input.as_bytes()), 11);
     }
 
     #[test]
@@ -176,6 +382,67 @@ a
 
 b";
 
-        assert_eq!(part_2(parse_input(input)), 6);
+        assert_eq!(part_2(input.as_bytes()), 6);
+    }
+
+    #[test]
+    fn test_quorum_queries() {
+        let group = GroupReader::new("abc\nab\nbd\nb".as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(group.questions(Quorum::Anyone), vec!['a', 'b', 'c', 'd']);
+        assert_eq!(group.questions(Quorum::Everyone), vec!['b']);
+        assert_eq!(group.questions(Quorum::AtLeast(2)), vec!['a', 'b']);
+        assert_eq!(group.questions(Quorum::Exactly(1)), vec!['c', 'd']);
+        assert_eq!(group.questions(Quorum::Nobody).len(), 22);
+        assert!(GroupReader::new("ab\nA".as_bytes())
+            .next()
+            .unwrap()
+            .is_err());
+        assert_eq!(
+            run_command(
+                &["questions".to_owned(), "everyone".to_owned()],
+                "abc\nab\n\nz".as_bytes()
+            )
+            .unwrap(),
+            "group 1: ab\ngroup 2: z"
+        );
+    }
+
+    #[test]
+    fn test_histograms() {
+        let input = "abc\n\na\nb\nc\n\n\nab\nac\n";
+        let groups = GroupReader::new(input.as_bytes())
+            .collect::<Result<Vec<GroupAnswers>, StringErr>>()
+            .unwrap();
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[2].histogram()[&'a'], 2);
+        assert_eq!(groups[2].histogram()[&'z'], 0);
+
+        let histogram = get_survey_histogram(GroupReader::new(input.as_bytes())).unwrap();
+        assert_eq!(
+            (histogram[&'a'], histogram[&'b'], histogram[&'c']),
+            (4, 3, 3)
+        );
+        assert_eq!(
+            count_answers(GroupReader::new(input.as_bytes()), Quorum::Exactly(1)).unwrap(),
+            3 + 3 + 2
+        );
+        assert!(count_answers(GroupReader::new("ab\n\nA".as_bytes()), Quorum::Anyone).is_err());
+    }
+
+    #[test]
+    fn test_answer_sets() {
+        let a = AnswerSet::parse("abz").unwrap();
+        let b = AnswerSet::parse("bcz").unwrap();
+
+        assert_eq!(a.0, 0b10_0000_0000_0000_0000_0000_0011);
+        assert_eq!(a.union(b), AnswerSet::parse("abcz").unwrap());
+        assert_eq!(a.intersection(b), AnswerSet::parse("bz").unwrap());
+        assert!(a.contains('z') && !a.contains('c'));
+        assert_eq!(a.union(b).len(), 4);
     }
 }
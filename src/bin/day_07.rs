@@ -1,40 +1,477 @@
-use std::collections::hash_map::Entry;
+use lazy_static::lazy_static;
+use regex::Regex;
 use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
 use std::fs;
-use std::iter::FromIterator;
+
+lazy_static! {
+    static ref RULE_RE: Regex = Regex::new(r"^(\w+ \w+) bags contain (.+)\.$").unwrap();
+    static ref CONTENTS_RE: Regex = Regex::new(r"^(\d+) (\w+ \w+) bags?$").unwrap();
+}
+
+#[derive(Debug)]
+struct StringErr(String);
+
+#[derive(Debug, PartialEq)]
+enum BagRuleError {
+    // the path starts and ends with the same color
+    Cycle(Vec<String>),
+    UndefinedColor {
+        color: String,
+        referenced_by: Vec<String>,
+    },
+    DuplicateRule {
+        color: String,
+        lines: Vec<usize>,
+    },
+    // the total number of bags inside the color does not fit in a u64
+    Overflow(String),
+}
+
+impl fmt::Display for BagRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BagRuleError::Cycle(path) => write!(f, "cycle: {}", path.join(" -> ")),
+            BagRuleError::UndefinedColor {
+                color,
+                referenced_by,
+            } => write!(
+                f,
+                "{} has no rule but is contained by {}",
+                color,
+                referenced_by.join(", ")
+            ),
+            BagRuleError::DuplicateRule { color, lines } => write!(
+                f,
+                "{} is defined more than once on lines {}",
+                color,
+                lines
+                    .iter()
+                    .map(|l| l.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            BagRuleError::Overflow(color) => {
+                write!(f, "{} contains more than {} bags", color, u64::MAX)
+            }
+        }
+    }
+}
+
+impl From<BagRuleError> for StringErr {
+    fn from(e: BagRuleError) -> Self {
+        StringErr(e.to_string())
+    }
+}
 
 fn main() {
     let data = fs::read_to_string("inputs/day_07.txt").expect("Unable to read file");
+    let graph = parse_input(&data);
+
+    // `day_07 <ancestors|descendants|contents> <color>` queries any color, not just shiny gold
+    // `day_07 totals` lists the contents of every color, `day_07 validate` checks the
+    // rules for cycles, undefined colors and duplicates and
+    // `day_07 export <dot|json> [from|to <color>]` writes the rules as a graph
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        match run_query(&graph, &args) {
+            Ok(output) => println!("{}", output),
+            Err(StringErr(e)) => eprintln!("{}", e),
+        }
+        return;
+    }
 
-    println!("Part 1: {}", part_1(parse_input(data.clone())));
-    println!("Part 2: {}", part_2(parse_input(data.clone())));
+    println!("Part 1: {}", part_1(&graph));
+    println!("Part 2: {}", part_2(&graph));
 }
 
-fn parse_input(input: String) -> Vec<(String, Vec<(String, i32)>)> {
-    input
-        .lines()
-        .map(|l| l.trim())
-        .filter(|&l| l.is_empty() || !l.contains("contain no other bags"))
-        .map(|l| {
-            let instruction: Vec<&str> = l.split("contain").collect();
-            let container = instruction[0]
-                .split(' ')
-                .take(2)
-                .collect::<Vec<&str>>()
-                .join("_");
-            let bags = instruction[1]
-                .split(',')
-                .map(|contains| {
-                    let c = contains.trim().split(' ').collect::<Vec<&str>>();
-                    (
-                        String::from(c[1..3].join("_")),
-                        c[0].parse::<i32>().unwrap(),
-                    )
-                })
-                .collect::<Vec<(String, i32)>>();
-            (container, bags)
+fn parse_input(input: &str) -> BagGraph {
+    BagGraph::parse(input).expect("Unable to parse rules")
+}
+
+fn run_query(graph: &BagGraph, args: &[String]) -> Result<String, StringErr> {
+    if args[0] == "export" {
+        return export(graph, &args[1..]);
+    }
+
+    let color = args[1..].join(" ");
+    let names = |ids: HashSet<ColorId>| {
+        let mut names = ids.iter().map(|&id| graph.name(id)).collect::<Vec<&str>>();
+        names.sort_unstable();
+        names.join("\n")
+    };
+
+    match args[0].as_str() {
+        "ancestors" => Ok(names(graph.ancestors(graph.id(&color)?))),
+        "descendants" => Ok(names(graph.descendants(graph.id(&color)?))),
+        "contents" => Ok(graph.total_contents(graph.id(&color)?)?.to_string()),
+        "totals" => {
+            let totals = graph.all_total_contents()?;
+            let mut lines = (0..graph.colors.len())
+                .map(|id| format!("{}: {}", graph.name(id), totals[id]))
+                .collect::<Vec<String>>();
+            lines.sort_unstable();
+            Ok(lines.join("\n"))
+        }
+        "validate" => match graph.validate() {
+            Ok(()) => Ok("ok".to_owned()),
+            Err(errors) => Err(StringErr(
+                errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join("\n"),
+            )),
+        },
+        command => Err(StringErr(format!("unknown query: {}", command))),
+    }
+}
+
+fn export(graph: &BagGraph, args: &[String]) -> Result<String, StringErr> {
+    let members = match args.get(1).map(|a| a.as_str()) {
+        None => vec![true; graph.colors.len()],
+        Some("from") => graph.subgraph(graph.id(&args[2..].join(" "))?, Direction::From),
+        Some("to") => graph.subgraph(graph.id(&args[2..].join(" "))?, Direction::To),
+        Some(direction) => return Err(StringErr(format!("unknown direction: {}", direction))),
+    };
+
+    match args.first().map(|a| a.as_str()) {
+        Some("dot") => Ok(graph.to_dot(&members)),
+        Some("json") => Ok(graph.to_json(&members)),
+        format => Err(StringErr(format!("unknown export format: {:?}", format))),
+    }
+}
+
+type ColorId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    // the color and every bag inside it
+    From,
+    // the color and every bag that can hold it
+    To,
+}
+
+// colors are interned in the order they are first seen, `contents` is the forward
+// adjacency (bag -> bags inside it) and `containers` the reverse. `rule_lines`
+// holds the line numbers each color's rule was defined on
+#[derive(Debug, Default)]
+struct BagGraph {
+    colors: Vec<String>,
+    ids: HashMap<String, ColorId>,
+    contents: Vec<Vec<(ColorId, u64)>>,
+    containers: Vec<Vec<ColorId>>,
+    rule_lines: Vec<Vec<usize>>,
+}
+
+impl BagGraph {
+    fn parse(input: &str) -> Result<BagGraph, StringErr> {
+        let mut graph = BagGraph::default();
+
+        for (line_number, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error =
+                |reason: &str| StringErr(format!("line {}: {}: {}", line_number + 1, reason, line));
+
+            let rule = RULE_RE
+                .captures(line)
+                .ok_or_else(|| error("expected `<color> bags contain ...`"))?;
+            let container = graph.intern(&rule[1]);
+            graph.rule_lines[container].push(line_number + 1);
+
+            if &rule[2] == "no other bags" {
+                continue;
+            }
+
+            for item in rule[2].split(", ") {
+                let contents = CONTENTS_RE
+                    .captures(item)
+                    .ok_or_else(|| error("expected `<count> <color> bags`"))?;
+                let count = contents[1]
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|&c| c > 0)
+                    .ok_or_else(|| error("bag count must be a positive number"))?;
+
+                let bag = graph.intern(&contents[2]);
+                graph.contents[container].push((bag, count));
+                graph.containers[bag].push(container);
+            }
+        }
+
+        Ok(graph)
+    }
+
+    fn intern(&mut self, color: &str) -> ColorId {
+        if let Some(&id) = self.ids.get(color) {
+            return id;
+        }
+
+        let id = self.colors.len();
+        self.colors.push(color.to_owned());
+        self.ids.insert(color.to_owned(), id);
+        self.contents.push(Vec::new());
+        self.containers.push(Vec::new());
+        self.rule_lines.push(Vec::new());
+        id
+    }
+
+    fn id(&self, color: &str) -> Result<ColorId, StringErr> {
+        self.ids
+            .get(color)
+            .cloned()
+            .ok_or_else(|| StringErr(format!("unknown color: {}", color)))
+    }
+
+    fn name(&self, id: ColorId) -> &str {
+        &self.colors[id]
+    }
+
+    // every bag that can eventually contain `color`
+    fn ancestors(&self, color: ColorId) -> HashSet<ColorId> {
+        self.reachable(color, |id| self.containers[id].clone())
+    }
+
+    // every bag that `color` eventually contains
+    fn descendants(&self, color: ColorId) -> HashSet<ColorId> {
+        self.reachable(color, |id| {
+            self.contents[id].iter().map(|&(bag, _)| bag).collect()
         })
-        .collect()
+    }
+
+    fn reachable(
+        &self,
+        color: ColorId,
+        next: impl Fn(ColorId) -> Vec<ColorId>,
+    ) -> HashSet<ColorId> {
+        let mut seen = HashSet::new();
+        let mut stack = next(color);
+
+        while let Some(id) = stack.pop() {
+            if seen.insert(id) {
+                stack.extend(next(id));
+            }
+        }
+
+        seen
+    }
+
+    fn subgraph(&self, color: ColorId, direction: Direction) -> Vec<bool> {
+        let reachable = match direction {
+            Direction::From => self.descendants(color),
+            Direction::To => self.ancestors(color),
+        };
+
+        let mut members = vec![false; self.colors.len()];
+        members[color] = true;
+        reachable.into_iter().for_each(|id| members[id] = true);
+        members
+    }
+
+    // edges between the colors in `members`, in rule order
+    fn edges<'a>(
+        &'a self,
+        members: &'a [bool],
+    ) -> impl Iterator<Item = (ColorId, ColorId, u64)> + 'a {
+        (0..self.colors.len())
+            .filter(move |&id| members[id])
+            .flat_map(move |id| {
+                self.contents[id]
+                    .iter()
+                    .filter(move |&&(bag, _)| members[bag])
+                    .map(move |&(bag, count)| (id, bag, count))
+            })
+    }
+
+    fn to_dot(&self, members: &[bool]) -> String {
+        let mut dot = String::from("digraph bags {\n");
+        (0..self.colors.len())
+            .filter(|&id| members[id])
+            .for_each(|id| dot += &format!("    \"{}\";\n", self.name(id)));
+        self.edges(members).for_each(|(container, bag, count)| {
+            dot += &format!(
+                "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                self.name(container),
+                self.name(bag),
+                count
+            )
+        });
+        dot + "}\n"
+    }
+
+    // { color: [{ "color": bag, "count": n }, ...], ... }
+    fn to_json(&self, members: &[bool]) -> String {
+        let adjacency = (0..self.colors.len())
+            .filter(|&id| members[id])
+            .map(|id| {
+                let contents = self.contents[id]
+                    .iter()
+                    .filter(|&&(bag, _)| members[bag])
+                    .map(|&(bag, count)| {
+                        format!("{{\"color\":\"{}\",\"count\":{}}}", self.name(bag), count)
+                    })
+                    .collect::<Vec<String>>();
+                format!("\"{}\":[{}]", self.name(id), contents.join(","))
+            })
+            .collect::<Vec<String>>();
+
+        format!("{{{}}}\n", adjacency.join(","))
+    }
+
+    // total number of bags inside `color`
+    fn total_contents(&self, color: ColorId) -> Result<u64, BagRuleError> {
+        let mut members = vec![false; self.colors.len()];
+        members[color] = true;
+        self.descendants(color)
+            .into_iter()
+            .for_each(|id| members[id] = true);
+
+        Ok(self.count_totals(&members, &[color])?[color])
+    }
+
+    // total number of bags inside every color, indexed by ColorId
+    fn all_total_contents(&self) -> Result<Vec<u64>, BagRuleError> {
+        let roots = (0..self.colors.len()).collect::<Vec<ColorId>>();
+        self.count_totals(&vec![true; self.colors.len()], &roots)
+    }
+
+    // sums the colors in `members` leaves first, so every bag's total is known
+    // (memoized) before any bag containing it is reached
+    fn count_totals(&self, members: &[bool], roots: &[ColorId]) -> Result<Vec<u64>, BagRuleError> {
+        let mut totals = vec![0u64; self.colors.len()];
+        let mut pending = self
+            .contents
+            .iter()
+            .map(|contents| contents.len())
+            .collect::<Vec<usize>>();
+        let mut ready = (0..self.colors.len())
+            .filter(|&id| members[id] && pending[id] == 0)
+            .collect::<Vec<ColorId>>();
+        let mut counted = 0;
+
+        while let Some(id) = ready.pop() {
+            counted += 1;
+            totals[id] = self.contents[id]
+                .iter()
+                .try_fold(0u64, |acc, &(bag, count)| {
+                    totals[bag]
+                        .checked_add(1)
+                        .and_then(|t| t.checked_mul(count))
+                        .and_then(|t| t.checked_add(acc))
+                })
+                .ok_or_else(|| BagRuleError::Overflow(self.name(id).to_owned()))?;
+
+            for &container in &self.containers[id] {
+                pending[container] -= 1;
+                if members[container] && pending[container] == 0 {
+                    ready.push(container);
+                }
+            }
+        }
+
+        // anything left waiting is on or above a cycle
+        if counted < members.iter().filter(|&&m| m).count() {
+            let cycle = self.find_cycles(roots).into_iter().next().unwrap();
+            return Err(BagRuleError::Cycle(cycle));
+        }
+
+        Ok(totals)
+    }
+
+    fn validate(&self) -> Result<(), Vec<BagRuleError>> {
+        let mut errors = Vec::new();
+
+        for (id, lines) in self.rule_lines.iter().enumerate() {
+            match lines.len() {
+                0 => errors.push(BagRuleError::UndefinedColor {
+                    color: self.name(id).to_owned(),
+                    referenced_by: self.containers[id]
+                        .iter()
+                        .map(|&c| self.name(c).to_owned())
+                        .collect(),
+                }),
+                1 => {}
+                _ => errors.push(BagRuleError::DuplicateRule {
+                    color: self.name(id).to_owned(),
+                    lines: lines.clone(),
+                }),
+            }
+        }
+
+        let roots = (0..self.colors.len()).collect::<Vec<ColorId>>();
+        errors.extend(
+            self.find_cycles(&roots)
+                .into_iter()
+                .map(BagRuleError::Cycle),
+        );
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // iterative depth first search over the contents, every edge back onto the
+    // current path closes a cycle
+    fn find_cycles(&self, roots: &[ColorId]) -> Vec<Vec<String>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            New,
+            OnPath,
+            Done,
+        }
+
+        let mut marks = vec![Mark::New; self.colors.len()];
+        let mut cycles = Vec::new();
+
+        for &root in roots {
+            if marks[root] != Mark::New {
+                continue;
+            }
+
+            marks[root] = Mark::OnPath;
+            let mut path: Vec<(ColorId, usize)> = vec![(root, 0)];
+
+            while let Some(&(id, edge)) = path.last() {
+                let next = match self.contents[id].get(edge) {
+                    Some(&(next, _)) => next,
+                    None => {
+                        marks[id] = Mark::Done;
+                        path.pop();
+                        continue;
+                    }
+                };
+                path.last_mut().unwrap().1 += 1;
+
+                match marks[next] {
+                    Mark::New => {
+                        marks[next] = Mark::OnPath;
+                        path.push((next, 0));
+                    }
+                    Mark::OnPath => {
+                        let start = path.iter().position(|&(p, _)| p == next).unwrap();
+                        cycles.push(
+                            path[start..]
+                                .iter()
+                                .map(|&(p, _)| p)
+                                .chain(std::iter::once(next))
+                                .map(|p| self.name(p).to_owned())
+                                .collect(),
+                        );
+                    }
+                    Mark::Done => {}
+                }
+            }
+        }
+
+        cycles
+    }
 }
 
 /*
@@ -71,45 +508,8 @@ So, in this example, the number of bag colors that can eventually contain at lea
 
 How many bag colors can eventually contain at least one shiny gold bag? (The list of rules is quite long; make sure you get all of it.)
  */
-fn part_1(input: Vec<(String, Vec<(String, i32)>)>) -> i32 {
-    // build a reverse index { bag_x: Set(every bag that can contain bag_x) }
-    let mapping: HashMap<String, HashSet<String>> =
-        input
-            .iter()
-            .fold(HashMap::new(), |mut acc, (container, bags)| {
-                bags.iter()
-                    .for_each(|(bag, _)| match acc.entry(String::from(bag)) {
-                        Entry::Vacant(e) => {
-                            e.insert(HashSet::from_iter(vec![container.to_owned()].into_iter()));
-                        }
-                        Entry::Occupied(mut e) => {
-                            e.get_mut().insert(container.to_owned());
-                        }
-                    });
-                acc
-            });
-
-    bag_has_containers(&mapping, String::from("shiny_gold"), &mut HashSet::new())
-}
-
-fn bag_has_containers(
-    mapping: &HashMap<String, HashSet<String>>,
-    color: String,
-    containers_accumulator: &mut HashSet<String>,
-) -> i32 {
-    if !mapping.contains_key(&color) {
-        return containers_accumulator.len() as i32;
-    }
-
-    mapping[&color]
-        .iter()
-        .filter(|contents| contents.to_string() != color.to_string()) // avoid recursion
-        .for_each(|color| {
-            containers_accumulator.insert(color.to_string());
-            bag_has_containers(mapping, String::from(color), containers_accumulator);
-        });
-
-    containers_accumulator.len() as i32
+fn part_1(graph: &BagGraph) -> usize {
+    graph.ancestors(graph.id("shiny gold").unwrap()).len()
 }
 
 /*
@@ -142,27 +542,10 @@ In this example, a single shiny gold bag must contain 126 other bags.
 
 How many individual bags are required inside your single shiny gold bag?
  */
-fn part_2(input: Vec<(String, Vec<(String, i32)>)>) -> i32 {
-    let mapping: HashMap<String, Vec<(String, i32)>> =
-        input.iter().fold(HashMap::new(), |mut acc, x| {
-            acc.insert(x.0.clone(), x.1.clone());
-            acc
-        });
-
-    bag_contains(String::from("shiny_gold"), &mapping)
-}
-
-fn bag_contains(bag: String, mapping: &HashMap<String, Vec<(String, i32)>>) -> i32 {
-    if !mapping.contains_key(&bag) {
-        return 0;
-    }
-
-    mapping[&bag]
-        .iter()
-        .map(|(contained_bag, count)| {
-            count + count * bag_contains(String::from(contained_bag), mapping)
-        })
-        .sum()
+fn part_2(graph: &BagGraph) -> u64 {
+    graph
+        .total_contents(graph.id("shiny gold").unwrap())
+        .unwrap()
 }
 
 #[cfg(test)]
@@ -181,7 +564,7 @@ vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
 faded blue bags contain no other bags.
 dotted black bags contain no other bags.";
 
-        assert_eq!(part_1(parse_input(input.parse().unwrap())), 4);
+        assert_eq!(part_1(&parse_input(input)), 4);
     }
 
     #[test]
@@ -194,6 +577,194 @@ dark green bags contain 2 dark blue bags.
 dark blue bags contain 2 dark violet bags.
 dark violet bags contain no other bags.";
 
-        assert_eq!(part_2(parse_input(input.parse().unwrap())), 126);
+        assert_eq!(part_2(&parse_input(input)), 126);
+    }
+
+    #[test]
+    fn test_graph_queries() {
+        let graph = parse_input(
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 dark olive bag.
+dark olive bags contain 3 faded blue bags.
+faded blue bags contain no other bags.",
+        );
+        let names = |ids: HashSet<ColorId>| {
+            let mut names = ids.iter().map(|&id| graph.name(id)).collect::<Vec<&str>>();
+            names.sort_unstable();
+            names
+        };
+
+        assert_eq!(graph.colors.len(), 6);
+        assert_eq!(
+            names(graph.ancestors(graph.id("dark olive").unwrap())),
+            vec!["bright white", "light red", "muted yellow", "shiny gold"]
+        );
+        assert_eq!(
+            names(graph.descendants(graph.id("muted yellow").unwrap())),
+            vec!["dark olive", "faded blue", "shiny gold"]
+        );
+        assert_eq!(graph.total_contents(graph.id("faded blue").unwrap()), Ok(0));
+        assert_eq!(
+            graph.total_contents(graph.id("muted yellow").unwrap()),
+            Ok(2 + 2 * 4 + 9)
+        );
+        assert_eq!(graph.validate(), Ok(()));
+        assert!(graph.id("plaid purple").is_err());
+    }
+
+    #[test]
+    fn test_strict_parsing() {
+        assert!(BagGraph::parse("light red bags contain 1 bright white bag").is_err());
+        assert!(BagGraph::parse("light red bags contain 0 bright white bags.").is_err());
+        assert!(BagGraph::parse("light red bags hold 1 bright white bag.").is_err());
+
+        match BagGraph::parse(
+            "faded blue bags contain no other bags.\nlight red bags contain some bags.",
+        ) {
+            Err(StringErr(e)) => assert!(e.starts_with("line 2:")),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        let graph = parse_input(
+            "light red bags contain 1 bright white bag.
+bright white bags contain 2 muted yellow bags, 1 plaid purple bag.
+muted yellow bags contain 1 light red bag.
+dark olive bags contain 1 dark olive bag.
+faded blue bags contain no other bags.
+faded blue bags contain 1 bright white bag.",
+        );
+
+        assert_eq!(
+            graph.validate(),
+            Err(vec![
+                BagRuleError::UndefinedColor {
+                    color: "plaid purple".to_owned(),
+                    referenced_by: vec!["bright white".to_owned()],
+                },
+                BagRuleError::DuplicateRule {
+                    color: "faded blue".to_owned(),
+                    lines: vec![5, 6],
+                },
+                BagRuleError::Cycle(vec![
+                    "light red".to_owned(),
+                    "bright white".to_owned(),
+                    "muted yellow".to_owned(),
+                    "light red".to_owned(),
+                ]),
+                BagRuleError::Cycle(vec!["dark olive".to_owned(), "dark olive".to_owned()]),
+            ])
+        );
+        assert_eq!(
+            graph
+                .total_contents(graph.id("faded blue").unwrap())
+                .unwrap_err()
+                .to_string(),
+            "cycle: bright white -> muted yellow -> light red -> bright white"
+        );
+        assert_eq!(
+            graph.total_contents(graph.id("plaid purple").unwrap()),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn test_all_totals() {
+        let graph = parse_input(
+            "shiny gold bags contain 2 dark red bags.
+dark red bags contain 2 dark orange bags.
+dark orange bags contain 2 dark yellow bags.
+dark yellow bags contain 2 dark green bags.
+dark green bags contain 2 dark blue bags.
+dark blue bags contain 2 dark violet bags.
+dark violet bags contain no other bags.",
+        );
+
+        assert_eq!(
+            graph.all_total_contents(),
+            Ok(vec![126, 62, 30, 14, 6, 2, 0])
+        );
+    }
+
+    #[test]
+    fn test_deep_rules() {
+        let depth = 20_000;
+        let rules = (0..depth)
+            .map(|i| format!("pale c{} bags contain 1 pale c{} bag.", i, i + 1))
+            .chain(std::iter::once(format!(
+                "pale c{} bags contain no other bags.",
+                depth
+            )))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let graph = parse_input(&rules);
+
+        assert_eq!(
+            graph.total_contents(graph.id("pale c0").unwrap()),
+            Ok(depth)
+        );
+        assert_eq!(graph.all_total_contents().unwrap()[1], depth - 1);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let rules = (0..70)
+            .map(|i| format!("pale c{} bags contain 2 pale c{} bags.", i, i + 1))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let graph = parse_input(&rules);
+
+        assert_eq!(
+            graph.total_contents(graph.id("pale c0").unwrap()),
+            Err(BagRuleError::Overflow("pale c6".to_owned()))
+        );
+        assert_eq!(
+            graph.total_contents(graph.id("pale c10").unwrap()),
+            Ok((1 << 61) - 2)
+        );
+    }
+
+    #[test]
+    fn test_export() {
+        let graph = parse_input(
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 dark olive bag.
+dark olive bags contain no other bags.
+faded blue bags contain no other bags.",
+        );
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<String>>();
+
+        assert_eq!(
+            export(&graph, &args("dot from muted yellow")).unwrap(),
+            "digraph bags {
+    \"muted yellow\";
+    \"shiny gold\";
+    \"faded blue\";
+    \"dark olive\";
+    \"muted yellow\" -> \"shiny gold\" [label=\"2\"];
+    \"muted yellow\" -> \"faded blue\" [label=\"9\"];
+    \"shiny gold\" -> \"dark olive\" [label=\"1\"];
+}
+"
+        );
+        assert_eq!(
+            export(&graph, &args("json to bright white")).unwrap(),
+            "{\"light red\":[{\"color\":\"bright white\",\"count\":1}],\"bright white\":[]}\n"
+        );
+        assert_eq!(
+            export(&graph, &args("dot"))
+                .unwrap()
+                .matches(" -> ")
+                .count(),
+            6
+        );
+        assert!(export(&graph, &args("svg")).is_err());
+        assert!(export(&graph, &args("dot from plaid purple")).is_err());
     }
 }
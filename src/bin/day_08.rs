@@ -1,5 +1,8 @@
 use std::collections::HashSet;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::io::{self, BufRead, Write};
 
 #[derive(Debug)]
 struct StringErr(String);
@@ -7,79 +10,890 @@ struct StringErr(String);
 fn main() {
     let data = fs::read_to_string("inputs/day_08.txt").expect("Unable to read file");
 
+    // `day_08 disassemble` prints the assembled program back in its text format and
+    // `day_08 debug` starts an interactive debugger reading commands from stdin and
+    // `day_08 repairs` lists every single flip that makes the program terminate and
+    // `day_08 run <path> [error|wrap|halt] [budget]` runs another boot-code program
+    // with the extended instruction set and `day_08 analyze [text|json] [path]`
+    // reports on a program without running it
+    let args = env::args().collect::<Vec<String>>();
+    match args.get(1).map(|a| a.as_str()) {
+        Some("run") => {
+            match run_command(&args[2..]) {
+                Ok(output) => println!("{}", output),
+                Err(StringErr(e)) => eprintln!("{}", e),
+            }
+            return;
+        }
+        Some("disassemble") => {
+            match Program::assemble(&data) {
+                Ok(program) => print!("{}", program.disassemble()),
+                Err(StringErr(e)) => eprintln!("{}", e),
+            }
+            return;
+        }
+        Some("analyze") => {
+            match analyze_command(&args[2..], &data) {
+                Ok(output) => println!("{}", output),
+                Err(StringErr(e)) => eprintln!("{}", e),
+            }
+            return;
+        }
+        Some("repairs") => {
+            find_repairs(&parse_input(&data))
+                .iter()
+                .for_each(|repair| println!("{}", repair));
+            return;
+        }
+        Some("debug") => {
+            run_debugger(parse_input(&data), io::stdin().lock(), io::stdout());
+            return;
+        }
+        _ => {}
+    }
+
     println!("Part 1: {}", part_1(parse_input(data.as_str())));
     println!("Part 2: {}", part_2(parse_input(data.as_str())).unwrap());
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    Value(i32),
+    Acc,
+    // one of the 26 extra registers `a` to `z`
+    Register(char),
+}
+
+impl Operand {
+    fn parse(text: &str) -> Result<Operand, String> {
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            _ if text == "acc" => Ok(Operand::Acc),
+            (Some(c), None) if c.is_ascii_lowercase() => Ok(Operand::Register(c)),
+            _ => text
+                .parse::<i32>()
+                .map(Operand::Value)
+                .map_err(|_| format!("invalid argument `{}`", text)),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Value(value) => write!(f, "{:+}", value),
+            Operand::Acc => write!(f, "acc"),
+            Operand::Register(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+// an opcode added on top of nop, acc and jmp
+#[derive(Debug, Clone, Copy)]
+struct Opcode {
+    name: &'static str,
+    arity: usize,
+    // runs the instruction and returns how far to move ip
+    execute: fn(&mut Parser, &[Operand]) -> i32,
+    // the argument holding the offset, for opcodes that can jump
+    jump_argument: Option<usize>,
+}
+
+const MAX_ARITY: usize = 2;
+
+#[derive(Debug, Clone, Default)]
+struct InstructionSet {
+    opcodes: Vec<Opcode>,
+}
+
+impl InstructionSet {
+    // nop, acc and jmp plus `mul x`, `cpy x r`, `add x r` and `jnz x offset`
+    fn extended() -> InstructionSet {
+        let mut set = InstructionSet::default();
+        let opcodes = [
+            Opcode {
+                name: "mul",
+                arity: 1,
+                execute: |vm, args| {
+                    vm.acc = vm.acc.wrapping_mul(vm.read(args[0]));
+                    1
+                },
+                jump_argument: None,
+            },
+            Opcode {
+                name: "cpy",
+                arity: 2,
+                execute: |vm, args| {
+                    vm.write(args[1], vm.read(args[0]));
+                    1
+                },
+                jump_argument: None,
+            },
+            Opcode {
+                name: "add",
+                arity: 2,
+                execute: |vm, args| {
+                    vm.write(args[1], vm.read(args[1]).wrapping_add(vm.read(args[0])));
+                    1
+                },
+                jump_argument: None,
+            },
+            Opcode {
+                name: "jnz",
+                arity: 2,
+                execute: |vm, args| {
+                    if vm.read(args[0]) != 0 {
+                        vm.read(args[1])
+                    } else {
+                        1
+                    }
+                },
+                jump_argument: Some(1),
+            },
+        ];
+        for opcode in opcodes.iter() {
+            set.register(*opcode)
+                .expect("Built-in opcodes should register");
+        }
+        set
+    }
+
+    fn register(&mut self, opcode: Opcode) -> Result<(), StringErr> {
+        if ["nop", "acc", "jmp"].contains(&opcode.name) || self.get(opcode.name).is_some() {
+            return Err(StringErr(format!(
+                "opcode `{}` already exists",
+                opcode.name
+            )));
+        }
+        if opcode.arity > MAX_ARITY || opcode.jump_argument.is_some_and(|i| i >= opcode.arity) {
+            return Err(StringErr(format!(
+                "invalid arguments for `{}`",
+                opcode.name
+            )));
+        }
+        self.opcodes.push(opcode);
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Option<&Opcode> {
+        self.opcodes.iter().find(|opcode| opcode.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Instruction {
+    Nop(i32),
+    Acc(i32),
+    Jmp(i32),
+    // a registered opcode, with the arguments past `arity` left unused
+    Ext {
+        name: &'static str,
+        arity: usize,
+        args: [Operand; MAX_ARITY],
+        jump_argument: Option<usize>,
+    },
+}
+
+impl Instruction {
+    fn assemble(text: &str, set: &InstructionSet) -> Result<Instruction, String> {
+        let tokens = text.split_whitespace().collect::<Vec<&str>>();
+        let (op, arguments) = match tokens.split_first() {
+            Some((op, arguments)) => (*op, arguments),
+            None => return Err("expected `<op> <argument>`".to_owned()),
+        };
+
+        if let Some(opcode) = set.get(op) {
+            if arguments.len() != opcode.arity {
+                return Err(format!(
+                    "expected {} arguments for `{}`",
+                    opcode.arity, opcode.name
+                ));
+            }
+            let mut args = [Operand::Value(0); MAX_ARITY];
+            for (arg, text) in args.iter_mut().zip(arguments) {
+                *arg = Operand::parse(text)?;
+            }
+            return Ok(Instruction::Ext {
+                name: opcode.name,
+                arity: opcode.arity,
+                args,
+                jump_argument: opcode.jump_argument,
+            });
+        }
+
+        let argument = match arguments {
+            [argument] => *argument,
+            _ => return Err("expected `<op> <argument>`".to_owned()),
+        };
+
+        let value = argument
+            .parse::<i32>()
+            .map_err(|_| format!("invalid argument `{}`", argument))?;
+
+        match op {
+            "nop" => Ok(Instruction::Nop(value)),
+            "acc" => Ok(Instruction::Acc(value)),
+            "jmp" => Ok(Instruction::Jmp(value)),
+            _ => Err(format!("unknown op `{}`", op)),
+        }
+    }
+}
+
+impl Instruction {
+    fn name(&self) -> &'static str {
+        match self {
+            Instruction::Nop(_) => "nop",
+            Instruction::Acc(_) => "acc",
+            Instruction::Jmp(_) => "jmp",
+            Instruction::Ext { name, .. } => name,
+        }
+    }
+
+    // how far the instruction moves ip, registered opcodes that jump are only
+    // known at run time
+    fn offset(&self) -> Option<i32> {
+        match self {
+            Instruction::Jmp(value) => Some(*value),
+            Instruction::Ext {
+                jump_argument: Some(_),
+                ..
+            } => None,
+            _ => Some(1),
+        }
+    }
+
+    // every address the instruction can go to next, or None for a jump that reads
+    // its offset from a register
+    fn successors(&self, address: usize) -> Option<Vec<i64>> {
+        let after = |offset: i32| address as i64 + offset as i64;
+        match self {
+            Instruction::Ext {
+                args,
+                jump_argument: Some(i),
+                ..
+            } => match args[*i] {
+                Operand::Value(1) => Some(vec![after(1)]),
+                Operand::Value(offset) => Some(vec![after(1), after(offset)]),
+                _ => None,
+            },
+            _ => self.offset().map(|offset| vec![after(offset)]),
+        }
+    }
+
+    // how much the instruction adds to acc
+    fn acc(&self) -> i32 {
+        match self {
+            Instruction::Acc(value) => *value,
+            _ => 0,
+        }
+    }
+
+    fn flipped(&self) -> Option<Instruction> {
+        match self {
+            Instruction::Nop(value) => Some(Instruction::Jmp(*value)),
+            Instruction::Jmp(value) => Some(Instruction::Nop(*value)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Nop(value) => write!(f, "nop {:+}", value),
+            Instruction::Acc(value) => write!(f, "acc {:+}", value),
+            Instruction::Jmp(value) => write!(f, "jmp {:+}", value),
+            Instruction::Ext {
+                name, arity, args, ..
+            } => {
+                write!(f, "{}", name)?;
+                args[..*arity]
+                    .iter()
+                    .try_for_each(|arg| write!(f, " {}", arg))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Program {
+    instructions: Vec<Instruction>,
+}
+
+impl Program {
+    fn assemble(source: &str) -> Result<Program, StringErr> {
+        Program::assemble_with(source, &InstructionSet::default())
+    }
+
+    // blank lines are skipped but still counted for the line numbers in errors
+    fn assemble_with(source: &str, set: &InstructionSet) -> Result<Program, StringErr> {
+        let instructions = source
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+            .map(|(i, l)| {
+                Instruction::assemble(l, set)
+                    .map_err(|e| StringErr(format!("line {}: {}: {}", i + 1, e, l.trim())))
+            })
+            .collect::<Result<Vec<Instruction>, StringErr>>()?;
+
+        Ok(Program { instructions })
+    }
+
+    fn disassemble(&self) -> String {
+        self.instructions
+            .iter()
+            .map(|instruction| format!("{}\n", instruction))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.instructions.len()
+    }
+}
+
+// what to do with a jump that lands outside the program, other than the
+// address just past the last instruction which always terminates
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JumpPolicy {
+    Error,
+    Wrap,
+    Halt,
+}
+
+impl JumpPolicy {
+    fn parse(text: &str) -> Result<JumpPolicy, StringErr> {
+        match text {
+            "error" => Ok(JumpPolicy::Error),
+            "wrap" => Ok(JumpPolicy::Wrap),
+            "halt" => Ok(JumpPolicy::Halt),
+            _ => Err(StringErr(format!("unknown jump policy `{}`", text))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Config {
+    jumps: JumpPolicy,
+    step_budget: Option<usize>,
+    // stop when an instruction is about to run a second time, which only proves a
+    // loop while acc is the sole state that can change control flow
+    detect_loops: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            jumps: JumpPolicy::Halt,
+            step_budget: None,
+            detect_loops: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Running,
+    // the instruction at ip has already been run once
+    Looped,
+    Terminated,
+    // a jump to this address with `JumpPolicy::Error`
+    OutOfRange(i64),
+    // a jump to this address with `JumpPolicy::Halt`, unlike running off the end
+    // of the program this isn't a normal termination
+    Halted(i64),
+    BudgetExhausted,
+    // an opcode missing from the instruction set the vm was given
+    IllegalInstruction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TraceEntry {
+    ip: i32,
+    instruction: Instruction,
+    acc_before: i32,
+    acc_after: i32,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>5}  {}  acc {} -> {}",
+            self.ip, self.instruction, self.acc_before, self.acc_after
+        )
+    }
+}
+
 struct Parser {
     ip: i32,
     acc: i32,
-    ops: Vec<(String, i32)>,
+    registers: [i32; 26],
+    program: Program,
+    set: InstructionSet,
+    config: Config,
+    steps: usize,
     run_ops: HashSet<i32>,
-    is_looped: bool,
+    status: Status,
+    // only recorded once a debug session asks for it with `record_trace`
+    trace: Option<Vec<TraceEntry>>,
 }
 
 impl Parser {
-    fn new(ops: Vec<(String, i32)>) -> Self {
+    fn new(program: Program) -> Self {
+        Parser::with(program, InstructionSet::default(), Config::default())
+    }
+
+    fn with(program: Program, set: InstructionSet, config: Config) -> Self {
+        let status = if program.len() == 0 {
+            Status::Terminated
+        } else {
+            Status::Running
+        };
+
         Self {
             ip: 0,
             acc: 0,
-            ops,
+            registers: [0; 26],
+            program,
+            set,
+            config,
+            steps: 0,
             run_ops: Default::default(),
-            is_looped: false,
+            status,
+            trace: None,
+        }
+    }
+
+    fn record_trace(mut self) -> Self {
+        self.trace = Some(Vec::new());
+        self
+    }
+
+    fn read(&self, operand: Operand) -> i32 {
+        match operand {
+            Operand::Value(value) => value,
+            Operand::Acc => self.acc,
+            Operand::Register(name) => self.registers[(name as u8 - b'a') as usize],
+        }
+    }
+
+    // writing to a plain value does nothing
+    fn write(&mut self, operand: Operand, value: i32) {
+        match operand {
+            Operand::Value(_) => {}
+            Operand::Acc => self.acc = value,
+            Operand::Register(name) => self.registers[(name as u8 - b'a') as usize] = value,
         }
     }
 
     fn run(&mut self) -> &mut Parser {
+        while self.step() == Status::Running {}
+        self
+    }
+
+    // runs a single instruction, a program that has stopped stays put
+    fn step(&mut self) -> Status {
+        if self.status != Status::Running {
+            return self.status;
+        }
+
+        if self
+            .config
+            .step_budget
+            .is_some_and(|budget| self.steps >= budget)
+        {
+            self.status = Status::BudgetExhausted;
+            return self.status;
+        }
+
+        if self.config.detect_loops && !self.run_ops.insert(self.ip) {
+            self.status = Status::Looped;
+            return self.status;
+        }
+
+        let instruction = self.program.instructions[self.ip as usize];
+        let acc_before = self.acc;
+        let ip = self.ip;
+
+        let offset = match instruction {
+            Instruction::Nop(_) => 1,
+            Instruction::Acc(value) => {
+                self.acc = self.acc.wrapping_add(value);
+                1
+            }
+            Instruction::Jmp(value) => value,
+            Instruction::Ext {
+                name, arity, args, ..
+            } => match self.set.get(name) {
+                Some(opcode) => (opcode.execute)(self, &args[..arity]),
+                None => {
+                    self.status = Status::IllegalInstruction;
+                    return self.status;
+                }
+            },
+        };
+
+        self.steps += 1;
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEntry {
+                ip,
+                instruction,
+                acc_before,
+                acc_after: self.acc,
+            });
+        }
+
+        let len = self.program.len() as i64;
+        let next = ip as i64 + offset as i64;
+        if next == len {
+            self.ip = next as i32;
+            self.status = Status::Terminated;
+        } else if next >= 0 && next < len {
+            self.ip = next as i32;
+        } else {
+            match self.config.jumps {
+                JumpPolicy::Error => self.status = Status::OutOfRange(next),
+                JumpPolicy::Wrap => self.ip = next.rem_euclid(len) as i32,
+                JumpPolicy::Halt => self.status = Status::Halted(next),
+            }
+        }
+
+        self.status
+    }
+
+    fn next_instruction(&self) -> Option<Instruction> {
+        if let Status::Terminated | Status::OutOfRange(_) | Status::Halted(_) = self.status {
+            return None;
+        }
+        self.program.instructions.get(self.ip as usize).cloned()
+    }
+
+    fn get_result(&mut self) -> (i32, bool) {
+        (self.acc, self.status == Status::Looped)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Breakpoint {
+    Address(i32),
+    Opcode(String),
+}
+
+impl Breakpoint {
+    fn parse(text: &str, set: &InstructionSet) -> Result<Breakpoint, StringErr> {
+        if let Ok(address) = text.parse::<i32>() {
+            return Ok(Breakpoint::Address(address));
+        }
+        if !["nop", "acc", "jmp"].contains(&text) && set.get(text).is_none() {
+            return Err(StringErr(format!("unknown opcode `{}`", text)));
+        }
+        Ok(Breakpoint::Opcode(text.to_owned()))
+    }
+
+    fn matches(&self, ip: i32, instruction: &Instruction) -> bool {
+        match self {
+            Breakpoint::Address(address) => *address == ip,
+            Breakpoint::Opcode(op) => op == instruction.name(),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Address(address) => write!(f, "address {}", address),
+            Breakpoint::Opcode(op) => write!(f, "opcode {}", op),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn parse(text: &str) -> Result<Comparison, StringErr> {
+        match text {
+            "==" => Ok(Comparison::Eq),
+            "!=" => Ok(Comparison::Ne),
+            "<" => Ok(Comparison::Lt),
+            "<=" => Ok(Comparison::Le),
+            ">" => Ok(Comparison::Gt),
+            ">=" => Ok(Comparison::Ge),
+            _ => Err(StringErr(format!("unknown comparison `{}`", text))),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// a condition on acc, e.g. `acc >= 10`, which stops the debugger when it becomes true
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Watch {
+    comparison: Comparison,
+    value: i32,
+}
+
+impl Watch {
+    fn parse(args: &[&str]) -> Result<Watch, StringErr> {
+        let (comparison, value) = match args {
+            ["acc", comparison, value] => (*comparison, *value),
+            _ => return Err(StringErr("expected `watch acc <op> <value>`".to_owned())),
+        };
+
+        Ok(Watch {
+            comparison: Comparison::parse(comparison)?,
+            value: value
+                .parse::<i32>()
+                .map_err(|_| StringErr(format!("invalid value `{}`", value)))?,
+        })
+    }
+
+    fn holds(&self, acc: i32) -> bool {
+        match self.comparison {
+            Comparison::Eq => acc == self.value,
+            Comparison::Ne => acc != self.value,
+            Comparison::Lt => acc < self.value,
+            Comparison::Le => acc <= self.value,
+            Comparison::Gt => acc > self.value,
+            Comparison::Ge => acc >= self.value,
+        }
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "acc {} {}", self.comparison, self.value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Stop {
+    Status(Status),
+    Breakpoint(Breakpoint),
+    Watch(Watch),
+}
+
+struct Debugger {
+    vm: Parser,
+    breakpoints: Vec<Breakpoint>,
+    watches: Vec<Watch>,
+}
+
+impl Debugger {
+    fn new(program: Program) -> Self {
+        Self {
+            vm: Parser::new(program).record_trace(),
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+        }
+    }
+
+    // always runs at least one instruction so it can move off a breakpoint
+    fn resume(&mut self) -> Stop {
         loop {
-            let (op, value) = &self.ops[self.ip as usize];
-            if self.run_ops.contains(&self.ip) {
-                self.is_looped = true;
-                return self;
+            let held = self
+                .watches
+                .iter()
+                .map(|w| w.holds(self.vm.acc))
+                .collect::<Vec<bool>>();
+
+            let status = self.vm.step();
+            if status != Status::Running {
+                return Stop::Status(status);
             }
 
-            &self.run_ops.insert(self.ip);
+            if let Some((watch, _)) = self
+                .watches
+                .iter()
+                .zip(held)
+                .find(|(w, held)| !held && w.holds(self.vm.acc))
+            {
+                return Stop::Watch(*watch);
+            }
 
-            match op.as_str() {
-                "nop" => {
-                    self.ip += 1;
-                }
-                "acc" => {
-                    self.acc += value;
-                    self.ip += 1;
-                }
-                "jmp" => {
-                    self.ip += value;
-                }
-                _ => {
-                    panic!("unknown op")
+            if let Some(instruction) = self.vm.next_instruction() {
+                if let Some(breakpoint) = self
+                    .breakpoints
+                    .iter()
+                    .find(|b| b.matches(self.vm.ip, &instruction))
+                {
+                    return Stop::Breakpoint(breakpoint.clone());
                 }
             }
+        }
+    }
 
-            if self.ip > (self.ops.len() as i32) - 1 {
-                return self;
+    fn location(&self) -> String {
+        let next = match self.vm.next_instruction() {
+            Some(instruction) => instruction.to_string(),
+            None => "-".to_owned(),
+        };
+        format!(
+            "ip {} acc {} {:?} next: {}",
+            self.vm.ip, self.vm.acc, self.vm.status, next
+        )
+    }
+
+    fn execute(&mut self, line: &str) -> Result<String, StringErr> {
+        let args = line.split_whitespace().collect::<Vec<&str>>();
+
+        match args.as_slice() {
+            ["step"] | ["s"] => {
+                self.vm.step();
+                Ok(self.location())
+            }
+            ["step", count] | ["s", count] => {
+                let count = count
+                    .parse::<usize>()
+                    .map_err(|_| StringErr(format!("invalid count `{}`", count)))?;
+                for _ in 0..count {
+                    self.vm.step();
+                }
+                Ok(self.location())
+            }
+            ["continue"] | ["c"] => {
+                let stop = match self.resume() {
+                    Stop::Status(status) => format!("{:?}", status),
+                    Stop::Breakpoint(breakpoint) => format!("breakpoint at {}", breakpoint),
+                    Stop::Watch(watch) => format!("watch {}", watch),
+                };
+                Ok(format!("{}\n{}", stop, self.location()))
             }
+            ["break", target] | ["b", target] => {
+                let breakpoint = Breakpoint::parse(target, &self.vm.set)?;
+                let message = format!("breakpoint at {}", breakpoint);
+                self.breakpoints.push(breakpoint);
+                Ok(message)
+            }
+            ["watch", condition @ ..] | ["w", condition @ ..] => {
+                let watch = Watch::parse(condition)?;
+                self.watches.push(watch);
+                Ok(format!("watching {}", watch))
+            }
+            ["trace"] | ["t"] => Ok(self
+                .vm
+                .trace
+                .iter()
+                .flatten()
+                .map(|entry| entry.to_string())
+                .collect::<Vec<String>>()
+                .join("\n")),
+            ["print"] | ["p"] => Ok(self.location()),
+            ["list"] | ["l"] => Ok(self
+                .vm
+                .program
+                .instructions
+                .iter()
+                .enumerate()
+                .map(|(i, instruction)| {
+                    let marker = if i as i32 == self.vm.ip { ">" } else { " " };
+                    format!("{} {:>5}  {}", marker, i, instruction)
+                })
+                .collect::<Vec<String>>()
+                .join("\n")),
+            _ => Err(StringErr(format!("unknown command `{}`", line.trim()))),
         }
     }
+}
 
-    fn get_result(&mut self) -> (i32, bool) {
-        (self.acc, self.is_looped)
+// reads debugger commands one per line until `quit` or the end of the input
+fn run_debugger<R: BufRead, W: Write>(program: Program, input: R, mut output: W) {
+    let mut debugger = Debugger::new(program);
+    writeln!(output, "{}", debugger.location()).unwrap();
+
+    for line in input.lines() {
+        let line = line.expect("Unable to read command");
+        match line.trim() {
+            "" => continue,
+            "quit" | "q" => break,
+            command => match debugger.execute(command) {
+                Ok(message) => writeln!(output, "{}", message).unwrap(),
+                Err(StringErr(e)) => writeln!(output, "error: {}", e).unwrap(),
+            },
+        }
     }
 }
 
-fn parse_input(input: &str) -> Vec<(String, i32)> {
-    input
-        .lines()
-        .map(|l| l.trim())
-        .filter(|&l| !l.is_empty())
-        .map(|l| {
-            let instruction = l.split(" ").map(String::from).collect::<Vec<String>>();
-            (
-                String::from(&instruction[0]),
-                instruction[1].parse::<i32>().unwrap(),
-            )
-        })
-        .collect()
+fn run_command(args: &[String]) -> Result<String, StringErr> {
+    let path = args
+        .first()
+        .ok_or_else(|| StringErr("expected `run <path> [error|wrap|halt] [budget]`".to_owned()))?;
+    let source = fs::read_to_string(path).map_err(|e| StringErr(format!("{}: {}", path, e)))?;
+
+    let set = InstructionSet::extended();
+    let mut config = Config {
+        // registers can change where a jump goes, so repeating an address is fine
+        detect_loops: false,
+        step_budget: Some(1_000_000),
+        ..Config::default()
+    };
+    if let Some(policy) = args.get(1) {
+        config.jumps = JumpPolicy::parse(policy)?;
+    }
+    if let Some(budget) = args.get(2) {
+        config.step_budget = Some(
+            budget
+                .parse::<usize>()
+                .map_err(|_| StringErr(format!("invalid budget `{}`", budget)))?,
+        );
+    }
+
+    let program = Program::assemble_with(&source, &set)?;
+    let mut vm = Parser::with(program, set, config);
+    vm.run();
+
+    let registers = vm
+        .registers
+        .iter()
+        .zip(b'a'..=b'z')
+        .filter(|(&value, _)| value != 0)
+        .map(|(value, name)| format!(" {} {}", name as char, value))
+        .collect::<String>();
+    Ok(format!(
+        "{:?} after {} steps: ip {} acc {}{}",
+        vm.status, vm.steps, vm.ip, vm.acc, registers
+    ))
+}
+
+fn analyze_command(args: &[String], data: &str) -> Result<String, StringErr> {
+    let source = match args.get(1) {
+        Some(path) => {
+            fs::read_to_string(path).map_err(|e| StringErr(format!("{}: {}", path, e)))?
+        }
+        None => data.to_owned(),
+    };
+    let set = InstructionSet::extended();
+    let analysis = analyze(&Program::assemble_with(&source, &set)?, &set);
+
+    match args.first().map(|a| a.as_str()) {
+        None | Some("text") => Ok(analysis.to_text()),
+        Some("json") => Ok(analysis.to_json()),
+        Some(format) => Err(StringErr(format!("unknown format `{}`", format))),
+    }
+}
+
+fn parse_input(input: &str) -> Program {
+    Program::assemble(input).expect("Unable to assemble program")
 }
 
 /*
@@ -129,8 +943,8 @@ Immediately before the program would run an instruction a second time, the value
 
 Run your copy of the boot code. Immediately before any instruction is executed a second time, what value is in the accumulator?
  */
-fn part_1(operations: Vec<(String, i32)>) -> i32 {
-    let (result, _) = Parser::new(operations).run().get_result();
+fn part_1(program: Program) -> i32 {
+    let (result, _) = Parser::new(program).run().get_result();
     result
 }
 
@@ -173,27 +987,486 @@ After the last instruction (acc +6), the program terminates by attempting to run
 
 Fix the program so that it terminates normally by changing exactly one jmp (to nop) or nop (to jmp). What is the value of the accumulator after the program terminates?
  */
-fn part_2(operations: Vec<(String, i32)>) -> Result<i32, StringErr> {
-    // generate every iteration of jmp => nop, nop => jump
-    for (i, (op, _)) in operations.iter().enumerate() {
-        if op != "nop" && op != "jmp" {
+fn part_2(program: Program) -> Result<i32, StringErr> {
+    find_repairs(&program)
+        .first()
+        .map(|repair| repair.acc)
+        .ok_or_else(|| StringErr("no result".to_owned()))
+}
+
+// a single jmp <=> nop flip that makes the program terminate with `acc`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Repair {
+    address: usize,
+    from: Instruction,
+    to: Instruction,
+    acc: i32,
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>5}  {} => {}  acc {}",
+            self.address, self.from, self.to, self.acc
+        )
+    }
+}
+
+// works backwards over the control flow graph from the end of the program to find
+// every instruction that runs to termination (and the acc it adds on the way),
+// then walks the original path once looking for flips that land in that set.
+// every instruction has a single successor so those paths form a tree rooted at
+// the end, and a flip only works if its path doesn't run back through the flipped
+// instruction, i.e. the flipped instruction isn't an ancestor of where it lands.
+// only nop, acc and jmp are understood, see `run_repairs` for the other opcodes.
+fn find_repairs(program: &Program) -> Vec<Repair> {
+    // only running onto the address just past the last instruction terminates, so
+    // jumps anywhere else outside the program lead nowhere, and registered opcodes
+    // that jump are left out as their target isn't known
+    let end = program.len();
+    let target = |address: usize, instruction: &Instruction| -> Option<usize> {
+        instruction
+            .offset()
+            .map(|offset| address as i64 + offset as i64)
+            .filter(|&next| next >= 0 && next <= end as i64)
+            .map(|next| next as usize)
+    };
+
+    // node `end` stands for every address past the last instruction
+    let mut predecessors = vec![Vec::new(); end + 1];
+    for (address, instruction) in program.instructions.iter().enumerate() {
+        if let Some(next) = target(address, instruction) {
+            predecessors[next].push(address);
+        }
+    }
+
+    // `entered` and `left` number each node on the way into and out of the tree
+    let mut acc_to_end: Vec<Option<i32>> = vec![None; end + 1];
+    let mut entered = vec![0; end + 1];
+    let mut left = vec![0; end + 1];
+    acc_to_end[end] = Some(0);
+    let mut clock = 0;
+    let mut stack = vec![(end, false)];
+    while let Some((node, done)) = stack.pop() {
+        clock += 1;
+        if done {
+            left[node] = clock;
             continue;
         }
+        entered[node] = clock;
+        stack.push((node, true));
+        for &address in &predecessors[node] {
+            acc_to_end[address] =
+                acc_to_end[node].map(|acc| acc.wrapping_add(program.instructions[address].acc()));
+            stack.push((address, false));
+        }
+    }
+    let runs_through = |address: usize, next: usize| {
+        acc_to_end[address].is_some()
+            && entered[address] <= entered[next]
+            && left[next] <= left[address]
+    };
+
+    let mut repairs = Vec::new();
+    let mut visited = vec![false; end];
+    let mut address = 0;
+    let mut acc: i32 = 0;
+
+    while address < end && !visited[address] {
+        visited[address] = true;
+        let instruction = program.instructions[address];
+
+        if let Some(flipped) = instruction.flipped() {
+            if let Some(acc_after) = target(address, &flipped)
+                .filter(|&next| !runs_through(address, next))
+                .and_then(|next| acc_to_end[next])
+            {
+                repairs.push(Repair {
+                    address,
+                    from: instruction,
+                    to: flipped,
+                    acc: acc.wrapping_add(acc_after),
+                });
+            }
+        }
+
+        acc = acc.wrapping_add(instruction.acc());
+        address = match target(address, &instruction) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    repairs
+}
+
+// a run of instructions that is only entered at `start` and only left at `end`
+#[derive(Debug, Clone, PartialEq)]
+struct Block {
+    start: usize,
+    end: usize,
+    successors: Vec<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Analysis {
+    len: usize,
+    unreachable: Vec<usize>,
+    blocks: Vec<Block>,
+    // jumps to an address other than the one just past the last instruction
+    escapes: Vec<(usize, i64)>,
+    // jumps whose offset is read from a register
+    dynamic: Vec<usize>,
+    // groups of reachable instructions that can never get out once entered
+    loops: Vec<Vec<usize>>,
+    always_loops: bool,
+    patches: Vec<Repair>,
+    // flips still running after `PATCH_BUDGET` steps, which may or may not terminate
+    undecided_patches: Vec<usize>,
+}
+
+// the steps a patched program gets to terminate in before it's left undecided
+const PATCH_BUDGET: usize = 100_000;
+
+// registered opcodes can change acc and pick where a jump goes at run time, which
+// `find_repairs` can't see, so programs using them have every flip on their path
+// checked by running it. returns the repairs and the flips that ran out of steps.
+fn run_repairs(program: &Program, set: &InstructionSet) -> (Vec<Repair>, Vec<usize>) {
+    // an address running twice is only a loop if no jump reads its offset
+    let config = Config {
+        detect_loops: program
+            .instructions
+            .iter()
+            .all(|instruction| instruction.offset().is_some()),
+        step_budget: Some(PATCH_BUDGET),
+        ..Config::default()
+    };
+
+    let mut original = Parser::with(program.clone(), set.clone(), config).record_trace();
+    original.run();
+    let mut seen = HashSet::new();
+    let path = original
+        .trace
+        .iter()
+        .flatten()
+        .map(|entry| entry.ip as usize)
+        .filter(|&address| seen.insert(address))
+        .collect::<Vec<usize>>();
 
-        let mut ops = operations.clone();
-        match op.as_str() {
-            "nop" => ops[i].0 = "jmp".parse().unwrap(),
-            "jmp" => ops[i].0 = "nop".parse().unwrap(),
+    let mut repairs = Vec::new();
+    let mut undecided = Vec::new();
+    for address in path {
+        let instruction = program.instructions[address];
+        let flipped = match instruction.flipped() {
+            Some(flipped) => flipped,
+            None => continue,
+        };
+        let mut patched = program.clone();
+        patched.instructions[address] = flipped;
+
+        let mut vm = Parser::with(patched, set.clone(), config);
+        match vm.run().status {
+            Status::Terminated => repairs.push(Repair {
+                address,
+                from: instruction,
+                to: flipped,
+                acc: vm.acc,
+            }),
+            Status::BudgetExhausted => undecided.push(address),
             _ => {}
         }
+    }
+
+    (repairs, undecided)
+}
 
-        let (result, is_loop) = Parser::new(ops).run().get_result();
-        if !is_loop {
-            return Ok(result);
+// works on the control flow graph alone, so a conditional jump counts as going
+// both ways and a jump with a register offset as possibly going anywhere. only
+// the patches come from running the program, with the opcodes in `set`.
+fn analyze(program: &Program, set: &InstructionSet) -> Analysis {
+    let len = program.len();
+    let successors = program
+        .instructions
+        .iter()
+        .enumerate()
+        .map(|(address, instruction)| instruction.successors(address))
+        .collect::<Vec<Option<Vec<i64>>>>();
+    let inside = |target: i64| -> Option<usize> {
+        if target >= 0 && target < len as i64 {
+            Some(target as usize)
+        } else {
+            None
+        }
+    };
+
+    let mut reachable = vec![false; len];
+    let mut stack = if len > 0 { vec![0] } else { vec![] };
+    while let Some(address) = stack.pop() {
+        if reachable[address] {
+            continue;
+        }
+        reachable[address] = true;
+        match &successors[address] {
+            Some(targets) => stack.extend(targets.iter().filter_map(|&t| inside(t))),
+            None => stack.extend(0..len),
+        }
+    }
+
+    // an instruction can stop if some path leads out of the program
+    let mut predecessors = vec![Vec::new(); len];
+    let mut can_stop = vec![false; len];
+    for (address, targets) in successors.iter().enumerate() {
+        match targets {
+            Some(targets) => targets.iter().for_each(|&t| match inside(t) {
+                Some(next) => predecessors[next].push(address),
+                None => can_stop[address] = true,
+            }),
+            None => can_stop[address] = true,
         }
     }
+    let mut stack = (0..len).filter(|&a| can_stop[a]).collect::<Vec<usize>>();
+    while let Some(address) = stack.pop() {
+        for &previous in &predecessors[address] {
+            if !can_stop[previous] {
+                can_stop[previous] = true;
+                stack.push(previous);
+            }
+        }
+    }
+
+    // every successor of a trapped instruction is trapped as well, so each
+    // strongly connected group of them is a loop that never ends. the groups come
+    // from Tarjan's algorithm with an explicit stack of (address, next edge)
+    let trapped = (0..len)
+        .filter(|&a| reachable[a] && !can_stop[a])
+        .collect::<Vec<usize>>();
+    let edges = |address: usize| -> Vec<usize> {
+        successors[address]
+            .iter()
+            .flatten()
+            .filter_map(|&t| inside(t))
+            .collect()
+    };
+    let mut index: Vec<Option<usize>> = vec![None; len];
+    let mut low = vec![0; len];
+    let mut on_stack = vec![false; len];
+    let mut stack = Vec::new();
+    let mut counter = 0;
+    let mut loops = Vec::new();
+    for &root in &trapped {
+        if index[root].is_some() {
+            continue;
+        }
+        let mut work = vec![(root, 0)];
+        while let Some((address, edge)) = work.pop() {
+            if edge == 0 {
+                index[address] = Some(counter);
+                low[address] = counter;
+                counter += 1;
+                stack.push(address);
+                on_stack[address] = true;
+            }
+
+            let targets = edges(address);
+            if let Some(&target) = targets.get(edge) {
+                work.push((address, edge + 1));
+                match index[target] {
+                    None => work.push((target, 0)),
+                    Some(i) if on_stack[target] => low[address] = low[address].min(i),
+                    Some(_) => {}
+                }
+                continue;
+            }
+
+            if let Some(&(parent, _)) = work.last() {
+                low[parent] = low[parent].min(low[address]);
+            }
+            if index[address] == Some(low[address]) {
+                let mut group = Vec::new();
+                while let Some(other) = stack.pop() {
+                    on_stack[other] = false;
+                    group.push(other);
+                    if other == address {
+                        break;
+                    }
+                }
+                // a lone instruction is only a loop if it jumps to itself
+                if group.len() > 1 || targets.contains(&address) {
+                    group.sort_unstable();
+                    loops.push(group);
+                }
+            }
+        }
+    }
+    loops.sort();
+
+    let mut leaders = vec![false; len + 1];
+    leaders[0] = true;
+    leaders[len] = true;
+    for (address, instruction) in program.instructions.iter().enumerate() {
+        if instruction.offset() != Some(1) || successors[address].as_ref().map_or(0, Vec::len) > 1 {
+            leaders[address + 1] = true;
+            for target in successors[address].iter().flatten() {
+                if let Some(next) = inside(*target) {
+                    leaders[next] = true;
+                }
+            }
+        }
+    }
+    let starts = (0..=len).filter(|&a| leaders[a]).collect::<Vec<usize>>();
+    let blocks = starts
+        .windows(2)
+        .map(|w| Block {
+            start: w[0],
+            end: w[1] - 1,
+            successors: successors[w[1] - 1].clone().unwrap_or_default(),
+        })
+        .collect();
+
+    let extended = program
+        .instructions
+        .iter()
+        .any(|instruction| matches!(instruction, Instruction::Ext { .. }));
+    let (patches, undecided_patches) = if extended {
+        run_repairs(program, set)
+    } else {
+        (find_repairs(program), Vec::new())
+    };
+
+    Analysis {
+        len,
+        unreachable: (0..len).filter(|&a| !reachable[a]).collect(),
+        blocks,
+        escapes: successors
+            .iter()
+            .enumerate()
+            .flat_map(|(address, targets)| {
+                targets
+                    .iter()
+                    .flatten()
+                    .filter(|&&t| t < 0 || t > len as i64)
+                    .map(move |&t| (address, t))
+            })
+            .collect(),
+        dynamic: (0..len).filter(|&a| successors[a].is_none()).collect(),
+        loops,
+        always_loops: len > 0 && !can_stop[0],
+        patches,
+        undecided_patches,
+    }
+}
+
+impl Analysis {
+    fn describe_target(&self, target: i64) -> String {
+        if target == self.len as i64 {
+            "end".to_owned()
+        } else {
+            target.to_string()
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let addresses = |addresses: &[usize]| match addresses {
+            [] => "-".to_owned(),
+            _ => addresses
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<String>>()
+                .join(" "),
+        };
+        let mut lines = vec![
+            format!("instructions {}", self.len),
+            format!("always loops {}", self.always_loops),
+            format!("unreachable {}", addresses(&self.unreachable)),
+            format!("dynamic jumps {}", addresses(&self.dynamic)),
+            "blocks".to_owned(),
+        ];
+        for block in &self.blocks {
+            let successors = block
+                .successors
+                .iter()
+                .map(|&t| self.describe_target(t))
+                .collect::<Vec<String>>();
+            lines.push(format!(
+                "{:>5} ..= {:<5} -> {}",
+                block.start,
+                block.end,
+                successors.join(" ")
+            ));
+        }
+        lines.push("escapes".to_owned());
+        for (address, target) in &self.escapes {
+            lines.push(format!("{:>5} -> {}", address, target));
+        }
+        lines.push("loops".to_owned());
+        for group in &self.loops {
+            lines.push(format!("    {}", addresses(group)));
+        }
+        lines.push(format!(
+            "undecided patches {}",
+            addresses(&self.undecided_patches)
+        ));
+        lines.push("patches".to_owned());
+        for repair in &self.patches {
+            lines.push(repair.to_string());
+        }
+        lines.join("\n")
+    }
+
+    fn to_json(&self) -> String {
+        let list = |items: Vec<String>| format!("[{}]", items.join(","));
+        let addresses =
+            |addresses: &[usize]| list(addresses.iter().map(|a| a.to_string()).collect());
+
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|block| {
+                format!(
+                    "{{\"start\":{},\"end\":{},\"successors\":{}}}",
+                    block.start,
+                    block.end,
+                    list(
+                        block
+                            .successors
+                            .iter()
+                            .map(|&t| format!("\"{}\"", self.describe_target(t)))
+                            .collect()
+                    )
+                )
+            })
+            .collect();
+        let escapes = self
+            .escapes
+            .iter()
+            .map(|(address, target)| format!("{{\"address\":{},\"target\":{}}}", address, target))
+            .collect();
+        let loops = self.loops.iter().map(|group| addresses(group)).collect();
+        let patches = self
+            .patches
+            .iter()
+            .map(|repair| {
+                format!(
+                    "{{\"address\":{},\"from\":\"{}\",\"to\":\"{}\",\"acc\":{}}}",
+                    repair.address, repair.from, repair.to, repair.acc
+                )
+            })
+            .collect();
 
-    Err(StringErr("no result".to_owned()))
+        format!(
+            "{{\"instructions\":{},\"always_loops\":{},\"unreachable\":{},\"dynamic_jumps\":{},\"blocks\":{},\"escapes\":{},\"loops\":{},\"patches\":{},\"undecided_patches\":{}}}",
+            self.len,
+            self.always_loops,
+            addresses(&self.unreachable),
+            addresses(&self.dynamic),
+            list(blocks),
+            list(escapes),
+            list(loops),
+            list(patches),
+            addresses(&self.undecided_patches)
+        )
+    }
 }
 
 #[cfg(test)]
@@ -227,4 +1500,401 @@ jmp -4
 acc +6";
         assert_eq!(part_2(parse_input(input)).unwrap(), 8)
     }
+
+    #[test]
+    fn test_assembler_round_trip() {
+        let input = "nop +0
+acc +1
+jmp -4
+";
+        let program = parse_input(input);
+
+        assert_eq!(
+            program.instructions,
+            vec![
+                Instruction::Nop(0),
+                Instruction::Acc(1),
+                Instruction::Jmp(-4)
+            ]
+        );
+        assert_eq!(program.disassemble(), input);
+        assert_eq!(Program::assemble(&program.disassemble()).unwrap(), program);
+    }
+
+    #[test]
+    fn test_assembler_errors() {
+        let error = |source: &str| match Program::assemble(source) {
+            Err(StringErr(e)) => e,
+            Ok(_) => panic!("expected an assembler error"),
+        };
+
+        assert_eq!(
+            error("nop +0\n\nmul +2"),
+            "line 3: unknown op `mul`: mul +2"
+        );
+        assert_eq!(error("acc one"), "line 1: invalid argument `one`: acc one");
+        assert_eq!(
+            error("nop +0\njmp"),
+            "line 2: expected `<op> <argument>`: jmp"
+        );
+    }
+
+    #[test]
+    fn test_extended_assembler() {
+        let set = InstructionSet::extended();
+        let input = "cpy +3 a\nmul a\nadd -1 a\njnz a -2\nacc +1\n";
+        let program = Program::assemble_with(input, &set).unwrap();
+
+        assert_eq!(
+            program.instructions[0],
+            Instruction::Ext {
+                name: "cpy",
+                arity: 2,
+                args: [Operand::Value(3), Operand::Register('a')],
+                jump_argument: None
+            }
+        );
+        assert_eq!(program.disassemble(), input);
+
+        let error = |source: &str| match Program::assemble_with(source, &set) {
+            Err(StringErr(e)) => e,
+            Ok(_) => panic!("expected an assembler error"),
+        };
+        assert_eq!(
+            error("jnz a"),
+            "line 1: expected 2 arguments for `jnz`: jnz a"
+        );
+        assert_eq!(error("mul ab"), "line 1: invalid argument `ab`: mul ab");
+        assert!(Program::assemble("mul +2").is_err());
+    }
+
+    #[test]
+    fn test_register_opcode() {
+        let mut set = InstructionSet::extended();
+        let double = Opcode {
+            name: "dbl",
+            arity: 1,
+            execute: |vm, args| {
+                vm.write(args[0], vm.read(args[0]) * 2);
+                1
+            },
+            jump_argument: None,
+        };
+
+        assert!(set.register(double).is_ok());
+        assert!(set.register(double).is_err());
+        assert!(set
+            .register(Opcode {
+                name: "acc",
+                ..double
+            })
+            .is_err());
+        assert!(set
+            .register(Opcode {
+                name: "jz",
+                jump_argument: Some(1),
+                ..double
+            })
+            .is_err());
+
+        let program = Program::assemble_with("acc +3\ndbl acc\ndbl acc", &set).unwrap();
+        let mut vm = Parser::with(program, set, Config::default());
+        assert_eq!(vm.run().get_result(), (12, false));
+        assert_eq!(vm.status, Status::Terminated);
+    }
+
+    #[test]
+    fn test_extended_program() {
+        // acc = 1 * 3 * 2 * 1 with `a` counting down
+        let set = InstructionSet::extended();
+        let program =
+            Program::assemble_with("acc +1\ncpy +3 a\nmul a\nadd -1 a\njnz a -2", &set).unwrap();
+
+        let mut vm = Parser::with(program.clone(), set.clone(), Config::default());
+        assert_eq!(vm.run().status, Status::Looped);
+
+        let config = Config {
+            detect_loops: false,
+            ..Config::default()
+        };
+        let mut vm = Parser::with(program.clone(), set.clone(), config);
+        assert_eq!(vm.run().status, Status::Terminated);
+        assert_eq!((vm.acc, vm.registers[0], vm.steps), (6, 0, 11));
+
+        let config = Config {
+            step_budget: Some(4),
+            ..config
+        };
+        let mut vm = Parser::with(program.clone(), set, config);
+        assert_eq!(vm.run().status, Status::BudgetExhausted);
+        assert_eq!((vm.ip, vm.steps), (4, 4));
+
+        // the vm was not given the opcodes the program uses
+        let mut vm = Parser::with(program, InstructionSet::default(), config);
+        assert_eq!(vm.run().status, Status::IllegalInstruction);
+    }
+
+    #[test]
+    fn test_jump_policies() {
+        let program = parse_input("acc +1\njmp -2\nacc +5");
+        let with_policy = |jumps| {
+            let config = Config {
+                jumps,
+                detect_loops: false,
+                step_budget: Some(10),
+            };
+            let mut vm = Parser::with(program.clone(), InstructionSet::default(), config);
+            vm.run();
+            (vm.status, vm.acc)
+        };
+
+        assert_eq!(with_policy(JumpPolicy::Error), (Status::OutOfRange(-1), 1));
+        assert_eq!(with_policy(JumpPolicy::Halt), (Status::Halted(-1), 1));
+        // -1 wraps round to `acc +5` and then runs off the end
+        assert_eq!(with_policy(JumpPolicy::Wrap), (Status::Terminated, 6));
+        assert_eq!(Parser::new(program).run().get_result(), (1, false));
+    }
+
+    #[test]
+    fn test_step_and_trace() {
+        let program = parse_input("nop +0\nacc +1\njmp +2\nacc -99\nacc +5");
+        assert_eq!(Parser::new(program.clone()).run().trace, None);
+
+        let mut vm = Parser::new(program).record_trace();
+
+        assert_eq!(vm.step(), Status::Running);
+        assert_eq!(vm.step(), Status::Running);
+        assert_eq!((vm.ip, vm.acc), (2, 1));
+        assert_eq!(vm.run().get_result(), (6, false));
+        assert_eq!(vm.status, Status::Terminated);
+        assert_eq!(vm.step(), Status::Terminated);
+        assert_eq!(
+            vm.trace.unwrap()[2..],
+            [
+                TraceEntry {
+                    ip: 2,
+                    instruction: Instruction::Jmp(2),
+                    acc_before: 1,
+                    acc_after: 1
+                },
+                TraceEntry {
+                    ip: 4,
+                    instruction: Instruction::Acc(5),
+                    acc_before: 1,
+                    acc_after: 6
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_debugger() {
+        let input = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+        let mut debugger = Debugger::new(parse_input(input));
+
+        debugger.execute("break 6").unwrap();
+        assert_eq!(debugger.resume(), Stop::Breakpoint(Breakpoint::Address(6)));
+        assert_eq!((debugger.vm.ip, debugger.vm.acc), (6, 1));
+
+        debugger.execute("b jmp").unwrap();
+        assert_eq!(
+            debugger.resume(),
+            Stop::Breakpoint(Breakpoint::Opcode("jmp".to_owned()))
+        );
+        assert_eq!((debugger.vm.ip, debugger.vm.acc), (7, 2));
+
+        debugger.execute("watch acc >= 4").unwrap();
+        assert_eq!(
+            debugger.resume(),
+            Stop::Watch(Watch {
+                comparison: Comparison::Ge,
+                value: 4
+            })
+        );
+        assert_eq!((debugger.vm.ip, debugger.vm.acc), (4, 5));
+        assert_eq!(debugger.resume(), Stop::Status(Status::Looped));
+        assert!(debugger.execute("watch acc ~ 4").is_err());
+        assert!(debugger.execute("break jnz").is_err());
+        assert!(debugger.execute("break acc").is_ok());
+        assert!(debugger.execute("jump").is_err());
+    }
+
+    #[test]
+    fn test_debugger_repl() {
+        let mut output = Vec::new();
+        run_debugger(
+            parse_input("acc +2\njmp -1"),
+            "step\n\nbogus\ntrace\nquit\nstep\n".as_bytes(),
+            &mut output,
+        );
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "ip 0 acc 0 Running next: acc +2
+ip 1 acc 2 Running next: jmp -1
+error: unknown command `bogus`
+    0  acc +2  acc 0 -> 2
+"
+        );
+    }
+
+    #[test]
+    fn test_analyze() {
+        let input = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+        let analysis = analyze(&parse_input(input), &InstructionSet::default());
+
+        assert_eq!(analysis.unreachable, vec![5, 8]);
+        assert_eq!(
+            analysis
+                .blocks
+                .iter()
+                .map(|b| (b.start, b.end, b.successors.clone()))
+                .collect::<Vec<(usize, usize, Vec<i64>)>>(),
+            vec![
+                (0, 0, vec![1]),
+                (1, 2, vec![6]),
+                (3, 4, vec![1]),
+                (5, 5, vec![6]),
+                (6, 7, vec![3]),
+                (8, 8, vec![9])
+            ]
+        );
+        assert_eq!(analysis.loops, vec![vec![1, 2, 3, 4, 6, 7]]);
+        assert!(analysis.always_loops);
+        assert!(analysis.escapes.is_empty());
+        assert_eq!(analysis.patches, find_repairs(&parse_input(input)));
+        assert!(analysis.undecided_patches.is_empty());
+        assert!(analysis
+            .to_json()
+            .starts_with("{\"instructions\":9,\"always_loops\":true,\"unreachable\":[5,8],"));
+    }
+
+    #[test]
+    fn test_analyze_escapes_and_branches() {
+        // the self loop at 3 is never entered, the jmp at 4 leaves the program
+        let analysis = analyze(
+            &parse_input("acc +1\njmp +3\nacc +2\njmp +0\njmp -7"),
+            &InstructionSet::default(),
+        );
+        assert_eq!(analysis.unreachable, vec![2, 3]);
+        assert_eq!(analysis.escapes, vec![(4, -3)]);
+        assert!(analysis.loops.is_empty());
+        assert!(!analysis.always_loops);
+
+        // a conditional jump can go either way, so only the jmp +0 is a sure loop
+        let set = InstructionSet::extended();
+        let program = Program::assemble_with("jnz a +2\njmp +0\njnz b c\nacc +1", &set).unwrap();
+        let analysis = analyze(&program, &set);
+        assert_eq!(analysis.loops, vec![vec![1]]);
+        // 0 can't get out either but is only on the way into the two loops
+        let program = Program::assemble_with("jnz a +3\njmp +1\njmp -1\njmp +0", &set).unwrap();
+        assert_eq!(analyze(&program, &set).loops, vec![vec![1, 2], vec![3]]);
+        assert_eq!(analysis.dynamic, vec![2]);
+        assert!(analysis.unreachable.is_empty());
+        assert!(!analysis.always_loops);
+        assert_eq!(
+            analysis.to_text().lines().take(4).collect::<Vec<&str>>(),
+            vec![
+                "instructions 4",
+                "always loops false",
+                "unreachable -",
+                "dynamic jumps 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analyze_extended_patches() {
+        let set = InstructionSet::extended();
+        let patches = |source: &str| {
+            let analysis = analyze(&Program::assemble_with(source, &set).unwrap(), &set);
+            let patches = analysis
+                .patches
+                .iter()
+                .map(|r| (r.address, r.acc))
+                .collect::<Vec<(usize, i32)>>();
+            (patches, analysis.undecided_patches)
+        };
+
+        // cpy writes acc, which only running the patched program picks up
+        assert_eq!(
+            patches("cpy +10 acc\njmp +0\nacc +1"),
+            (vec![(1, 11)], vec![])
+        );
+        // the jnz is taken, so the repair is past the conditional jump
+        assert_eq!(
+            patches("cpy +1 a\njnz a +2\nacc +5\njmp +0\nacc +1"),
+            (vec![(3, 1)], vec![])
+        );
+        // a counts up forever whichever way the jmp goes
+        assert_eq!(patches("add +1 a\njmp +1\njnz a -2"), (vec![], vec![1]));
+    }
+
+    #[test]
+    fn test_find_repairs() {
+        let input = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+        assert_eq!(
+            find_repairs(&parse_input(input)),
+            vec![Repair {
+                address: 7,
+                from: Instruction::Jmp(-4),
+                to: Instruction::Nop(-4),
+                acc: 8
+            }]
+        );
+
+        // both the first nop and the jmp at 2 can be flipped to reach the end
+        let repairs = find_repairs(&parse_input("nop +3\nacc +1\njmp -2\nacc +7"));
+        assert_eq!(
+            repairs
+                .iter()
+                .map(|r| (r.address, r.acc))
+                .collect::<Vec<(usize, i32)>>(),
+            vec![(0, 7), (2, 8)]
+        );
+        for repair in repairs {
+            let mut patched = parse_input("nop +3\nacc +1\njmp -2\nacc +7");
+            patched.instructions[repair.address] = repair.to;
+            assert_eq!(Parser::new(patched).run().get_result(), (repair.acc, false));
+        }
+
+        // jumping out of the program halts it rather than terminating it
+        for input in ["nop -1\njmp -1", "nop +5\njmp -1"].iter() {
+            assert_eq!(
+                find_repairs(&parse_input(input))
+                    .iter()
+                    .map(|r| r.address)
+                    .collect::<Vec<usize>>(),
+                vec![1]
+            );
+        }
+
+        // either flip sends the program back round through the flipped instruction
+        assert!(find_repairs(&parse_input("nop +2\njmp +2\njmp -2")).is_empty());
+    }
 }
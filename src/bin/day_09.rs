@@ -1,12 +1,45 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fmt;
 use std::fs;
+use std::io::{self, BufRead};
+use std::ops::RangeInclusive;
 
 #[derive(Debug)]
 struct StringErr(String);
 
 fn main() {
+    let args = env::args().collect::<Vec<String>>();
+
+    // `day_09 stream [preamble]` reads numbers from stdin and prints each invalid one
+    // as soon as it arrives
+    if let Some("stream") = args.get(1).map(|a| a.as_str()) {
+        let preamble_length = match parse_preamble_length(args.get(2)) {
+            Ok(length) => length,
+            Err(StringErr(e)) => return eprintln!("{}", e),
+        };
+        for result in validate_stream(io::stdin().lock(), preamble_length) {
+            match result {
+                Ok((position, number)) => println!("{} {}", position, number),
+                Err(StringErr(e)) => eprintln!("{}", e),
+            }
+        }
+        return;
+    }
+
     let data = fs::read_to_string("inputs/day_09.txt").expect("Unable to read file");
 
+    // `day_09 invalid [preamble]` lists the position and value of every invalid number,
+    // `day_09 range [preamble]` shows the range that adds up to the first one and
+    // `day_09 ranges [preamble]` lists every range that does
+    if args.len() > 1 {
+        match run_command(&args[1..], &data) {
+            Ok(output) => print!("{}", output),
+            Err(StringErr(e)) => eprintln!("{}", e),
+        }
+        return;
+    }
+
     println!("Part 1: {}", part_1(parse_input(data.as_str()), 25));
     println!(
         "Part 2: {}",
@@ -14,6 +47,41 @@ fn main() {
     );
 }
 
+fn parse_preamble_length(arg: Option<&String>) -> Result<usize, StringErr> {
+    match arg {
+        Some(length) => length
+            .parse::<usize>()
+            .ok()
+            .filter(|&length| length > 0)
+            .ok_or_else(|| StringErr(format!("invalid preamble length `{}`", length))),
+        None => Ok(25),
+    }
+}
+
+fn run_command(args: &[String], data: &str) -> Result<String, StringErr> {
+    let preamble_length = parse_preamble_length(args.get(1))?;
+
+    match args[0].as_str() {
+        "invalid" => Ok(invalid_numbers(parse_input(data), preamble_length)
+            .map(|(position, number)| format!("{} {}\n", position, number))
+            .collect()),
+        "range" => {
+            let inputs = parse_input(data);
+            let target = get_first_invalid_number(&inputs, preamble_length)?;
+            Ok(format!("{}\n", find_contiguous_range(&inputs, target)?))
+        }
+        "ranges" => {
+            let inputs = parse_input(data);
+            let target = get_first_invalid_number(&inputs, preamble_length)?;
+            Ok(contiguous_ranges(&inputs, target)
+                .iter()
+                .map(|range| format!("{}\n", range))
+                .collect())
+        }
+        command => Err(StringErr(format!("unknown command `{}`", command))),
+    }
+}
+
 fn parse_input(input: &str) -> Vec<i64> {
     input
         .lines()
@@ -23,38 +91,156 @@ fn parse_input(input: &str) -> Vec<i64> {
         .collect()
 }
 
-fn get_first_invalid_number(inputs: &Vec<i64>, preamble_length: usize) -> Result<i64, StringErr> {
-    for (i, input) in inputs.iter().enumerate() {
-        if i <= preamble_length {
-            continue;
+// the last `preamble_length` numbers and how many pairs of them with different
+// values add up to each sum, updated as numbers move through the window. pairs
+// whose sum overflows are left out as no i64 can be equal to it.
+struct XmasWindow {
+    preamble_length: usize,
+    window: VecDeque<i64>,
+    sums: HashMap<i64, usize>,
+}
+
+impl XmasWindow {
+    fn new(preamble_length: usize) -> Self {
+        Self {
+            preamble_length,
+            window: VecDeque::with_capacity(preamble_length + 1),
+            sums: HashMap::new(),
         }
+    }
 
-        let preamble = inputs
-            .split_at(i)
-            .0
-            .iter()
-            .rev()
-            .take(preamble_length)
-            .collect::<Vec<&i64>>();
+    fn is_full(&self) -> bool {
+        self.window.len() == self.preamble_length
+    }
+
+    fn is_valid(&self, number: i64) -> bool {
+        self.sums.contains_key(&number)
+    }
 
-        let combinations = get_combinations(preamble);
+    // whether the number is invalid, before it joins the window
+    fn check(&mut self, number: i64) -> bool {
+        let invalid = self.is_full() && !self.is_valid(number);
+        self.push(number);
+        invalid
+    }
 
-        if !combinations.contains(input) {
-            return Ok(*input);
+    fn push(&mut self, number: i64) {
+        for &other in self.window.iter().filter(|&&other| other != number) {
+            if let Some(sum) = number.checked_add(other) {
+                *self.sums.entry(sum).or_insert(0) += 1;
+            }
+        }
+        self.window.push_back(number);
+
+        if self.window.len() > self.preamble_length {
+            let oldest = self.window.pop_front().unwrap();
+            for &other in self.window.iter().filter(|&&other| other != oldest) {
+                let sum = match oldest.checked_add(other) {
+                    Some(sum) => sum,
+                    None => continue,
+                };
+                let count = self.sums.get_mut(&sum).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    self.sums.remove(&sum);
+                }
+            }
         }
     }
+}
 
-    Err(StringErr("no_result".to_owned()))
+// yields the position and value of every number that isn't the sum of two of
+// the numbers before it
+struct InvalidNumbers<I: Iterator<Item = i64>> {
+    numbers: I,
+    window: XmasWindow,
+    position: usize,
+}
+
+impl<I: Iterator<Item = i64>> Iterator for InvalidNumbers<I> {
+    type Item = (usize, i64);
+
+    fn next(&mut self) -> Option<(usize, i64)> {
+        for number in &mut self.numbers {
+            self.position += 1;
+            if self.window.check(number) {
+                return Some((self.position - 1, number));
+            }
+        }
+        None
+    }
+}
+
+fn invalid_numbers<I: IntoIterator<Item = i64>>(
+    numbers: I,
+    preamble_length: usize,
+) -> InvalidNumbers<I::IntoIter> {
+    InvalidNumbers {
+        numbers: numbers.into_iter(),
+        window: XmasWindow::new(preamble_length),
+        position: 0,
+    }
+}
+
+// reads one number per line from any input, holding nothing but the window and
+// the current line in memory
+struct StreamValidator<R: BufRead> {
+    reader: R,
+    line: String,
+    line_number: usize,
+    position: usize,
+    window: XmasWindow,
+}
+
+impl<R: BufRead> Iterator for StreamValidator<R> {
+    type Item = Result<(usize, i64), StringErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => self.line_number += 1,
+                Err(e) => return Some(Err(StringErr(e.to_string()))),
+            }
+
+            let text = self.line.trim();
+            if text.is_empty() {
+                continue;
+            }
+            let number = match text.parse::<i64>() {
+                Ok(number) => number,
+                Err(_) => {
+                    return Some(Err(StringErr(format!(
+                        "line {}: invalid number `{}`",
+                        self.line_number, text
+                    ))))
+                }
+            };
+
+            self.position += 1;
+            if self.window.check(number) {
+                return Some(Ok((self.position - 1, number)));
+            }
+        }
+    }
+}
+
+fn validate_stream<R: BufRead>(reader: R, preamble_length: usize) -> StreamValidator<R> {
+    StreamValidator {
+        reader,
+        line: String::new(),
+        line_number: 0,
+        position: 0,
+        window: XmasWindow::new(preamble_length),
+    }
 }
 
-fn get_combinations(preamble: Vec<&i64>) -> HashSet<i64> {
-    preamble
-        .iter()
-        .map(|&x| preamble.iter().map(|&y| x + y).collect::<HashSet<i64>>())
-        .fold(HashSet::new(), |mut acc, set| {
-            acc.extend(set.iter());
-            acc
-        })
+fn get_first_invalid_number(inputs: &[i64], preamble_length: usize) -> Result<i64, StringErr> {
+    invalid_numbers(inputs.iter().cloned(), preamble_length)
+        .next()
+        .map(|(_, number)| number)
+        .ok_or_else(|| StringErr("no_result".to_owned()))
 }
 
 /*
@@ -147,41 +333,250 @@ To find the encryption weakness, add together the smallest and largest number in
 What is the encryption weakness in your XMAS-encrypted list of numbers?
  */
 fn part_2(inputs: Vec<i64>, preamble_length: usize) -> Result<i64, StringErr> {
-    let invalid_number = get_first_invalid_number(&inputs, preamble_length).unwrap();
+    let invalid_number = get_first_invalid_number(&inputs, preamble_length)?;
+    find_contiguous_range(&inputs, invalid_number).map(|range| range.weakness())
+}
 
-    // uses a head (idx_head) and tail (idx_tail) pointer to iterate through
-    // the inputs list.
-    for (idx_head, input) in inputs.iter().enumerate() {
-        let mut idx_tail = idx_head + 1;
-        let mut considered_numbers: Vec<i64> = vec![*input];
+// at least two numbers in a row that add up to the target
+#[derive(Debug, Clone, PartialEq)]
+struct ContiguousRange {
+    range: RangeInclusive<usize>,
+    min: i64,
+    max: i64,
+}
 
-        loop {
-            let current_number = &inputs[idx_tail];
-            let rolling_sum: i64 = considered_numbers.iter().sum();
+impl ContiguousRange {
+    fn new(numbers: &[i64], range: RangeInclusive<usize>) -> Self {
+        let slice = &numbers[range.clone()];
+        Self {
+            min: *slice.iter().min().unwrap(),
+            max: *slice.iter().max().unwrap(),
+            range,
+        }
+    }
 
-            // success
-            if rolling_sum + current_number == invalid_number {
-                return Ok(considered_numbers.iter().min().unwrap()
-                    + considered_numbers.iter().max().unwrap());
-            }
+    fn weakness(&self) -> i64 {
+        self.min + self.max
+    }
+}
 
-            // moves the idx_head forward
-            if rolling_sum + current_number > invalid_number {
-                break;
-            }
+impl fmt::Display for ContiguousRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}..={} min {} max {} weakness {}",
+            self.range.start(),
+            self.range.end(),
+            self.min,
+            self.max,
+            self.weakness()
+        )
+    }
+}
+
+// grows the range at the end and shrinks it from the start while the sum is too
+// big, which only works because none of the numbers are negative. the sum is kept
+// as an i128 so it can't overflow however many numbers there are.
+fn find_contiguous_range(numbers: &[i64], target: i64) -> Result<ContiguousRange, StringErr> {
+    let target = target as i128;
+    let mut start = 0;
+    let mut sum = 0i128;
+
+    for (end, &number) in numbers.iter().enumerate() {
+        if number < 0 {
+            return Err(StringErr(format!("negative number at {}", end)));
+        }
+        sum += number as i128;
+
+        while sum > target && start < end {
+            sum -= numbers[start] as i128;
+            start += 1;
+        }
 
-            // moves the idx_tail forward
-            considered_numbers.push(*current_number);
-            idx_tail += 1;
+        if sum == target && start < end {
+            return Ok(ContiguousRange::new(numbers, start..=end));
         }
     }
 
     Err(StringErr("no_result".to_owned()))
 }
 
+// every range summing to the target, ordered by where it starts. uses the sums of
+// each prefix of the numbers as i128s, so negative numbers are fine here.
+fn contiguous_ranges(numbers: &[i64], target: i64) -> Vec<ContiguousRange> {
+    let mut prefix_starts: HashMap<i128, Vec<usize>> = HashMap::new();
+    let mut prefix = 0i128;
+    let mut ranges = Vec::new();
+    prefix_starts.insert(0, vec![0]);
+
+    for (end, &number) in numbers.iter().enumerate() {
+        prefix += number as i128;
+        if let Some(starts) = prefix_starts.get(&(prefix - target as i128)) {
+            for &start in starts.iter().filter(|&&start| start < end) {
+                ranges.push(ContiguousRange::new(numbers, start..=end));
+            }
+        }
+        prefix_starts.entry(prefix).or_default().push(end + 1);
+    }
+
+    ranges.sort_by_key(|r| (*r.range.start(), *r.range.end()));
+    ranges
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_find_contiguous_range() {
+        let numbers =
+            parse_input("35\n20\n15\n25\n47\n40\n62\n55\n65\n95\n102\n117\n150\n182\n127\n219");
+
+        assert_eq!(
+            find_contiguous_range(&numbers, 127).unwrap(),
+            ContiguousRange {
+                range: 2..=5,
+                min: 15,
+                max: 47
+            }
+        );
+        // 127 on its own is too short to count
+        assert_eq!(
+            contiguous_ranges(&numbers, 127)
+                .iter()
+                .map(|r| r.range.clone())
+                .collect::<Vec<RangeInclusive<usize>>>(),
+            vec![2..=5]
+        );
+        assert!(find_contiguous_range(&numbers, 1).is_err());
+        assert!(find_contiguous_range(&[1, -1, 2], 2).is_err());
+    }
+
+    #[test]
+    fn test_contiguous_ranges() {
+        // the last number completes the range, and overlapping ranges are all found
+        let numbers = vec![1, 2, 3, 0, 3, 2, 1];
+        assert_eq!(find_contiguous_range(&numbers, 6).unwrap().range, 0..=2);
+        assert_eq!(
+            contiguous_ranges(&numbers, 6)
+                .iter()
+                .map(|r| (r.range.clone(), r.weakness()))
+                .collect::<Vec<(RangeInclusive<usize>, i64)>>(),
+            vec![(0..=2, 4), (0..=3, 3), (2..=4, 3), (3..=6, 3), (4..=6, 4)]
+        );
+        assert_eq!(
+            contiguous_ranges(&[5, -2, 2, 5], 5)
+                .iter()
+                .map(|r| r.range.clone())
+                .collect::<Vec<RangeInclusive<usize>>>(),
+            vec![0..=2, 1..=3]
+        );
+    }
+
+    #[test]
+    fn test_ranges_past_i64() {
+        let numbers = vec![i64::MAX, i64::MAX, 1, 2, i64::MAX];
+        assert_eq!(find_contiguous_range(&numbers, 3).unwrap().range, 2..=3);
+        assert_eq!(
+            contiguous_ranges(&numbers, 3)
+                .iter()
+                .map(|r| r.range.clone())
+                .collect::<Vec<RangeInclusive<usize>>>(),
+            vec![2..=3]
+        );
+        // the two halves of i64::MIN only add up to it as a pair
+        let numbers = vec![i64::MIN / 2, i64::MIN / 2, i64::MAX, i64::MAX];
+        assert_eq!(
+            contiguous_ranges(&numbers, i64::MIN)
+                .iter()
+                .map(|r| r.range.clone())
+                .collect::<Vec<RangeInclusive<usize>>>(),
+            vec![0..=1]
+        );
+    }
+
+    #[test]
+    fn test_validate_stream() {
+        let input = "35\n20\n15\n25\n47\n\n40\n62\n55\n65\n95\n102\n117\n150\n182\n127\n219\n299\n277\n309\n576\nxyz\n1";
+        let results = validate_stream(input.as_bytes(), 5)
+            .map(|result| result.map_err(|StringErr(e)| e))
+            .collect::<Vec<Result<(usize, i64), String>>>();
+
+        assert_eq!(
+            results,
+            vec![
+                Ok((14, 127)),
+                Err("line 22: invalid number `xyz`".to_owned()),
+                Ok((20, 1))
+            ]
+        );
+
+        // sums that overflow never make a number valid
+        let input = format!("{}\n1\n{}\n", i64::MAX, i64::MIN);
+        assert_eq!(
+            validate_stream(input.as_bytes(), 2)
+                .map(|result| result.map_err(|StringErr(e)| e))
+                .collect::<Vec<Result<(usize, i64), String>>>(),
+            vec![Ok((2, i64::MIN))]
+        );
+        assert!(parse_preamble_length(Some(&"0".to_owned())).is_err());
+        assert!(run_command(&["invalid".to_owned(), "0".to_owned()], input.as_str()).is_err());
+        assert_eq!(
+            validate_stream(input.as_bytes(), 5)
+                .filter_map(Result::ok)
+                .collect::<Vec<(usize, i64)>>(),
+            invalid_numbers(parse_input(&input.replace("xyz\n", "")), 5)
+                .collect::<Vec<(usize, i64)>>()
+        );
+    }
+
+    #[test]
+    fn test_pairs_must_differ() {
+        let preamble = (1..=25).collect::<Vec<i64>>();
+        let is_valid = |number: i64| {
+            let mut numbers = preamble.clone();
+            numbers.push(number);
+            invalid_numbers(numbers, 25).next().is_none()
+        };
+
+        assert!(is_valid(26));
+        assert!(is_valid(49));
+        assert!(!is_valid(100));
+        assert!(!is_valid(50));
+        assert!(!is_valid(2));
+
+        // two 5s in the window still can't make 10, but a 5 and a 6 can make 11
+        let numbers = vec![5, 5, 6, 10, 11];
+        assert_eq!(
+            invalid_numbers(numbers, 3).collect::<Vec<(usize, i64)>>(),
+            vec![(3, 10)]
+        );
+    }
+
+    #[test]
+    fn test_sums_past_i64() {
+        // i64::MAX + 1 doesn't fit, and mustn't wrap round to pass i64::MIN
+        assert_eq!(
+            invalid_numbers(vec![i64::MAX, 1, i64::MIN], 2).collect::<Vec<(usize, i64)>>(),
+            vec![(2, i64::MIN)]
+        );
+        assert_eq!(
+            invalid_numbers(vec![i64::MAX, 1, -1, i64::MAX - 1, i64::MAX], 3)
+                .collect::<Vec<(usize, i64)>>(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_all_invalid_numbers() {
+        // an invalid number still joins the window for the ones after it
+        let numbers = vec![1, 2, 3, 100, 5, 103, 7];
+        assert_eq!(
+            invalid_numbers(numbers, 3).collect::<Vec<(usize, i64)>>(),
+            vec![(3, 100), (6, 7)]
+        );
+    }
+
     #[test]
     fn test_example_1() {
         let input = "35
@@ -1,24 +1,300 @@
-use itertools::Itertools;
-use std::collections::{HashMap, HashSet};
+use std::collections::BTreeMap;
+use std::env;
 use std::fs;
 
+#[derive(Debug)]
+struct StringErr(String);
+
 fn main() {
     let data = fs::read_to_string("inputs/day_10.txt").expect("Unable to read file");
 
-    println!("Part 1: {}", part_1(parse_input(data.as_str())));
-    println!("Part 2: {}", part_2(parse_input(data.as_str())));
+    // `day_10 histogram [gaps]` and `day_10 arrangements [gaps]` build the chain with
+    // another set of allowed gaps, e.g. `1,2,3,4`, `day_10 list <n> [gaps]` shows
+    // the first n arrangements and `day_10 sample <seed> [gaps]` a random one
+    let args = env::args().collect::<Vec<String>>();
+    if args.len() > 1 {
+        match run_command(&args[1..], &data) {
+            Ok(output) => println!("{}", output),
+            Err(StringErr(e)) => eprintln!("{}", e),
+        }
+        return;
+    }
+
+    println!("Part 1: {}", part_1(parse_input(data.as_str())).unwrap());
+    println!("Part 2: {}", part_2(parse_input(data.as_str())).unwrap());
+}
+
+fn run_command(args: &[String], data: &str) -> Result<String, StringErr> {
+    // `list` and `sample` take a count or seed before the gaps
+    let (command, value, gaps) = match args {
+        [command, rest @ ..] if command == "list" || command == "sample" => {
+            let value = rest
+                .first()
+                .ok_or_else(|| StringErr(format!("expected `{} <number> [gaps]`", command)))?;
+            let value = value
+                .parse::<u64>()
+                .map_err(|_| StringErr(format!("invalid number `{}`", value)))?;
+            (command.as_str(), value, rest.get(1))
+        }
+        [command, rest @ ..] => (command.as_str(), 0, rest.first()),
+        [] => return Err(StringErr("expected a command".to_owned())),
+    };
+    let allowed_gaps = match gaps {
+        Some(gaps) => gaps
+            .split(',')
+            .map(|gap| {
+                gap.trim()
+                    .parse::<i32>()
+                    .map_err(|_| StringErr(format!("invalid gap `{}`", gap)))
+            })
+            .collect::<Result<Vec<i32>, StringErr>>()?,
+        None => DEFAULT_GAPS.to_vec(),
+    };
+    let chain = AdapterChain::new(&parse_input(data), &allowed_gaps)?;
+    let show = |adapters: Vec<i32>| {
+        adapters
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+
+    match command {
+        "histogram" => Ok(chain
+            .differences()
+            .iter()
+            .map(|(gap, count)| format!("{} {}", gap, count))
+            .collect::<Vec<String>>()
+            .join("\n")),
+        "arrangements" => Ok(chain.count_arrangements()?.to_string()),
+        "list" => Ok(chain
+            .arrangements()
+            .take(value as usize)
+            .map(show)
+            .collect::<Vec<String>>()
+            .join("\n")),
+        "sample" => Ok(show(chain.sample_arrangement(random_numbers(value))?)),
+        command => Err(StringErr(format!("unknown command `{}`", command))),
+    }
 }
 
 fn parse_input(input: &str) -> Vec<i32> {
-    let mut inp: Vec<i32> = input
+    input
         .lines()
         .map(|l| l.trim())
         .filter(|l| !l.is_empty())
         .map(|l| l.parse::<i32>().unwrap())
-        .collect();
-    inp.push(0); // input is 0
-    inp.push(*inp.iter().max().unwrap() + 3); // output is max + 3
-    inp
+        .collect()
+}
+
+const DEFAULT_GAPS: [i32; 3] = [1, 2, 3];
+
+// the outlet, every adapter in ascending order and then the device, which is
+// rated the largest allowed gap above the highest adapter
+#[derive(Debug, Clone, PartialEq)]
+struct AdapterChain {
+    joltages: Vec<i32>,
+    allowed_gaps: Vec<i32>,
+}
+
+impl AdapterChain {
+    // every adapter has to be used, so each step up the chain has to be an allowed gap
+    fn new(adapters: &[i32], allowed_gaps: &[i32]) -> Result<AdapterChain, StringErr> {
+        let mut allowed_gaps = allowed_gaps.to_vec();
+        allowed_gaps.sort_unstable();
+        allowed_gaps.dedup();
+        let largest_gap = match allowed_gaps.last() {
+            Some(&gap) if allowed_gaps[0] >= 0 => gap,
+            _ => {
+                return Err(StringErr(
+                    "gaps must be a non-empty set of non-negative numbers".to_owned(),
+                ))
+            }
+        };
+
+        let mut joltages = vec![0];
+        joltages.extend(adapters);
+        joltages.sort_unstable();
+        joltages.push(joltages.last().unwrap() + largest_gap);
+
+        for (position, pair) in joltages.windows(2).enumerate() {
+            let gap = pair[1] - pair[0];
+            if allowed_gaps.binary_search(&gap).is_err() {
+                return Err(StringErr(format!(
+                    "no adapter fits gap {} of the chain: {} jolts to {} jolts is {} apart",
+                    position + 1,
+                    pair[0],
+                    pair[1],
+                    gap
+                )));
+            }
+        }
+
+        Ok(AdapterChain {
+            joltages,
+            allowed_gaps,
+        })
+    }
+
+    // how many times each gap comes up when every adapter is used
+    fn differences(&self) -> BTreeMap<i32, usize> {
+        let mut histogram = BTreeMap::new();
+        for pair in self.joltages.windows(2) {
+            *histogram.entry(pair[1] - pair[0]).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    // the joltages that can come straight after the one at `i`
+    fn next_steps(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        let largest_gap = *self.allowed_gaps.last().unwrap();
+        (i + 1..self.joltages.len())
+            .take_while(move |&j| self.joltages[j] - self.joltages[i] <= largest_gap)
+            .filter(move |&j| {
+                self.allowed_gaps
+                    .binary_search(&(self.joltages[j] - self.joltages[i]))
+                    .is_ok()
+            })
+    }
+
+    // the number of ways from each joltage to the device is the sum of the ways
+    // from the joltages it can step to
+    fn ways_to_device(&self) -> Result<Vec<u128>, StringErr> {
+        let last = self.joltages.len() - 1;
+        let mut ways = vec![0u128; self.joltages.len()];
+        ways[last] = 1;
+
+        for i in (0..last).rev() {
+            ways[i] = self
+                .next_steps(i)
+                .try_fold(0u128, |total, j| total.checked_add(ways[j]))
+                .ok_or_else(|| {
+                    StringErr(format!(
+                        "too many arrangements to count from {} jolts",
+                        self.joltages[i]
+                    ))
+                })?;
+        }
+
+        Ok(ways)
+    }
+
+    fn count_arrangements(&self) -> Result<u128, StringErr> {
+        Ok(self.ways_to_device()?[0])
+    }
+
+    fn arrangements(&self) -> Arrangements<'_> {
+        let last = self.joltages.len() - 1;
+        let mut reaches_device = vec![false; self.joltages.len()];
+        reaches_device[last] = true;
+        for i in (0..last).rev() {
+            reaches_device[i] = self.next_steps(i).any(|j| reaches_device[j]);
+        }
+
+        Arrangements {
+            chain: self,
+            stack: vec![(0, 1)],
+            reaches_device,
+        }
+    }
+
+    // the arrangement `arrangements()` would yield at `index`, found without
+    // going through the ones before it
+    fn nth_arrangement(&self, index: u128) -> Result<Option<Vec<i32>>, StringErr> {
+        let ways = self.ways_to_device()?;
+        if index >= ways[0] {
+            return Ok(None);
+        }
+
+        let last = self.joltages.len() - 1;
+        let mut index = index;
+        let mut adapters = Vec::new();
+        let mut i = 0;
+        while i != last {
+            for j in self.next_steps(i) {
+                if index < ways[j] {
+                    i = j;
+                    break;
+                }
+                index -= ways[j];
+            }
+            if i != last {
+                adapters.push(self.joltages[i]);
+            }
+        }
+
+        Ok(Some(adapters))
+    }
+
+    // picks an index with a 128 bit random number from `random`, retrying the ones
+    // that would favour the low indexes
+    fn sample_arrangement<F: FnMut() -> u128>(&self, mut random: F) -> Result<Vec<i32>, StringErr> {
+        let count = self.count_arrangements()?;
+        let limit = u128::MAX - u128::MAX % count;
+        loop {
+            let value = random();
+            if value < limit {
+                return Ok(self.nth_arrangement(value % count)?.unwrap());
+            }
+        }
+    }
+}
+
+// every way to connect the outlet to the device as the list of adapters used, in
+// ascending order, worked out one at a time with a depth first search
+struct Arrangements<'a> {
+    chain: &'a AdapterChain,
+    // each joltage on the current path and the next index to try after it
+    stack: Vec<(usize, usize)>,
+    reaches_device: Vec<bool>,
+}
+
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = Vec<i32>;
+
+    fn next(&mut self) -> Option<Vec<i32>> {
+        let last = self.chain.joltages.len() - 1;
+
+        while let Some(&(i, from)) = self.stack.last() {
+            if i == last {
+                let adapters = self.stack[1..self.stack.len() - 1]
+                    .iter()
+                    .map(|&(j, _)| self.chain.joltages[j])
+                    .collect();
+                self.stack.pop();
+                return Some(adapters);
+            }
+
+            match self
+                .chain
+                .next_steps(i)
+                .find(|&j| j >= from && self.reaches_device[j])
+            {
+                Some(j) => {
+                    self.stack.last_mut().unwrap().1 = j + 1;
+                    self.stack.push((j, j + 1));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// splitmix64, enough to pick arrangements without another dependency
+fn random_numbers(seed: u64) -> impl FnMut() -> u128 {
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    move || ((next() as u128) << 64) | next() as u128
 }
 
 /*
@@ -107,37 +383,9 @@ In this larger example, in a chain that uses all of the adapters, there are 22 d
 
 Find a chain that uses all of your adapters to connect the charging outlet to your device's built-in adapter and count the joltage differences between the charging outlet, the adapters, and your device. What is the number of 1-jolt differences multiplied by the number of 3-jolt differences?
  */
-fn part_1(inputs: Vec<i32>) -> i32 {
-    let goal = inputs.iter().max().unwrap().clone();
-    let hs: HashSet<&i32> = inputs.iter().collect();
-    let mut hm: HashMap<i32, i32> = HashMap::new(); // [differences: count]
-
-    let mut current_val = 0;
-    loop {
-        if current_val == goal {
-            break;
-        }
-
-        if hs.get(&(current_val + 1)).is_some() {
-            *hm.entry(1).or_insert(0) += 1;
-            current_val += 1;
-            continue;
-        }
-
-        if hs.get(&(current_val + 2)).is_some() {
-            *hm.entry(2).or_insert(0) += 1;
-            current_val += 2;
-            continue;
-        }
-
-        if hs.get(&(current_val + 3)).is_some() {
-            *hm.entry(3).or_insert(0) += 1;
-            current_val += 3;
-            continue;
-        }
-    }
-
-    hm.get(&1).unwrap() * hm.get(&3).unwrap()
+fn part_1(inputs: Vec<i32>) -> Result<usize, StringErr> {
+    let differences = AdapterChain::new(&inputs, &DEFAULT_GAPS)?.differences();
+    Ok(differences.get(&1).unwrap_or(&0) * differences.get(&3).unwrap_or(&0))
 }
 
 /*
@@ -196,27 +444,11 @@ You glance back down at your bag and try to remember why you brought so many ada
 
 What is the total number of distinct ways you can arrange the adapters to connect the charging outlet to your device?
  */
-fn part_2(inputs: Vec<i32>) -> i64 {
-    /*
-    Here we build a hashmap of the number of times each joltage can be connected to i.e. {joltage : number of times it can be connected to}.
-    We do this in ascending order to build a cumulative value.
-    i.e. given a joltage list [1,2,3] we know 1 contains a direct connection to 0 i.e. {1: 1}
-     2 contains all the connections 1 has, and a direct connection to 0 i.e. {1: 1, 2: 2}
-     3 contains all the connections 2 has, 1 has, and a direct connection to 0 i.e. {1: 1, 2: 2, 3: 4}
-    */
-    let mut hm: HashMap<i32, i64> = HashMap::new();
-    hm.insert(0, 1);
-    inputs.clone().iter().sorted().for_each(|&val| {
-        // look backwards at the last three values to see if any of the adapters can connect to this one
-        // adding the number of possible times it can be connected to
-        *hm.entry(val).or_insert(0) += hm.get(&(val - 1)).unwrap_or(&0i64)
-            + hm.get(&(val - 2)).unwrap_or(&0i64)
-            + hm.get(&(val - 3)).unwrap_or(&0i64)
-    });
-
-    *hm.get(inputs.iter().max().unwrap()).unwrap() // number of times the goal can be connected to
+fn part_2(inputs: Vec<i32>) -> Result<u128, StringErr> {
+    AdapterChain::new(&inputs, &DEFAULT_GAPS)?.count_arrangements()
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -233,7 +465,7 @@ mod tests {
 6
 12
 4";
-        assert_eq!(part_1(parse_input(input)), 35)
+        assert_eq!(part_1(parse_input(input)).unwrap(), 35)
     }
 
     #[test]
@@ -270,7 +502,7 @@ mod tests {
 10
 3
 ";
-        assert_eq!(part_1(parse_input(input)), 220)
+        assert_eq!(part_1(parse_input(input)).unwrap(), 220)
     }
 
     #[test]
@@ -286,7 +518,7 @@ mod tests {
 6
 12
 4";
-        assert_eq!(part_2(parse_input(input)), 8)
+        assert_eq!(part_2(parse_input(input)).unwrap(), 8)
     }
 
     #[test]
@@ -323,6 +555,98 @@ mod tests {
 10
 3
 ";
-        assert_eq!(part_2(parse_input(input)), 19208)
+        assert_eq!(part_2(parse_input(input)).unwrap(), 19208)
+    }
+
+    #[test]
+    fn test_allowed_gaps() {
+        let adapters = parse_input("16\n10\n15\n5\n1\n11\n7\n19\n6\n12\n4");
+        let chain = AdapterChain::new(&adapters, &DEFAULT_GAPS).unwrap();
+        assert_eq!(
+            chain
+                .differences()
+                .into_iter()
+                .collect::<Vec<(i32, usize)>>(),
+            vec![(1, 7), (3, 5)]
+        );
+
+        // with gaps of up to 4 the device sits 4 above the highest adapter
+        let chain = AdapterChain::new(&adapters, &[4, 1, 2, 3]).unwrap();
+        assert_eq!(chain.joltages.last(), Some(&23));
+        assert_eq!(
+            chain
+                .differences()
+                .into_iter()
+                .collect::<Vec<(i32, usize)>>(),
+            vec![(1, 7), (3, 4), (4, 1)]
+        );
+
+        // only steps of one or three, so 4 -> 5 -> 6 -> 7 can't skip any adapter
+        let chain = AdapterChain::new(&adapters, &[1, 3]).unwrap();
+        assert_eq!(chain.count_arrangements().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_broken_chain() {
+        let error = |adapters: &[i32], gaps: &[i32]| match AdapterChain::new(adapters, gaps) {
+            Err(StringErr(e)) => e,
+            Ok(_) => panic!("expected a broken chain"),
+        };
+
+        assert_eq!(
+            error(&[1, 2, 7, 8], &DEFAULT_GAPS),
+            "no adapter fits gap 3 of the chain: 2 jolts to 7 jolts is 5 apart"
+        );
+        assert_eq!(
+            error(&[2, 4], &[1]),
+            "no adapter fits gap 1 of the chain: 0 jolts to 2 jolts is 2 apart"
+        );
+        assert!(AdapterChain::new(&[1], &[]).is_err());
+        assert!(AdapterChain::new(&[1], &[-1, 1]).is_err());
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let adapters = parse_input("16\n10\n15\n5\n1\n11\n7\n19\n6\n12\n4");
+        let chain = AdapterChain::new(&adapters, &DEFAULT_GAPS).unwrap();
+        let arrangements = chain.arrangements().collect::<Vec<Vec<i32>>>();
+
+        assert_eq!(arrangements.len(), 8);
+        assert_eq!(arrangements[0], vec![1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19]);
+        assert_eq!(arrangements[7], vec![1, 4, 7, 10, 12, 15, 16, 19]);
+        for (index, arrangement) in arrangements.iter().enumerate() {
+            assert_eq!(
+                chain.nth_arrangement(index as u128).unwrap().as_ref(),
+                Some(arrangement)
+            );
+        }
+        assert_eq!(chain.nth_arrangement(8).unwrap(), None);
+        assert!(arrangements.contains(&chain.sample_arrangement(random_numbers(7)).unwrap()));
+
+        // 3 can't reach the device with steps of 2, so it is never tried
+        let chain = AdapterChain::new(&[2, 3, 4], &[1, 2]).unwrap();
+        assert_eq!(
+            chain.arrangements().collect::<Vec<Vec<i32>>>(),
+            vec![vec![2, 3, 4], vec![2, 4]]
+        );
+    }
+
+    #[test]
+    fn test_arrangement_overflow() {
+        // with gaps of up to 200 every subset of 1..=200 that keeps the highest
+        // adapter is an arrangement
+        let adapters = (1..=200).collect::<Vec<i32>>();
+        let gaps = (1..=200).collect::<Vec<i32>>();
+        let chain = AdapterChain::new(&adapters, &gaps).unwrap();
+
+        assert!(chain.count_arrangements().is_err());
+        assert_eq!(chain.arrangements().next().unwrap(), adapters);
+        assert_eq!(
+            AdapterChain::new(&adapters[..100], &gaps)
+                .unwrap()
+                .count_arrangements()
+                .unwrap(),
+            1 << 99
+        );
     }
 }
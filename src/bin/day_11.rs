@@ -1,31 +1,144 @@
-use itertools::Itertools;
-use lazy_static::lazy_static;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+#[derive(Debug)]
+struct StringErr(String);
+
+const SURROUNDING_DELTAS: [(i32, i32); 8] = [
+    (0, -1),
+    (0, 1),
+    (-1, 0),
+    (1, 0),
+    (-1, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+];
 
-enum Change {
-    FILL,
-    ABANDON,
-    NONE,
+fn main() {
+    let data = fs::read_to_string("inputs/day_11.txt").expect("Unable to read file");
+
+    // `day_11 life <rule> <rounds> <path>` runs a Game of Life rule string such as
+    // `B3/S23` on the pattern in a file of `#` and `.` cells and
+    // `day_11 frames <text|ndjson|ppm> [part] [directory]` records every round of
+    // the seating simulation, with ppm writing one image per round, and
+    // `day_11 outcome [part] [threads]` says whether the seats settle or cycle
+    let args = env::args().collect::<Vec<String>>();
+    if args.get(1).map(|a| a.as_str()) == Some("frames") {
+        if let Err(StringErr(e)) = frames_command(&args[2..], io::stdout().lock()) {
+            eprintln!("{}", e);
+        }
+        return;
+    }
+    if args.len() > 1 {
+        match run_command(&args[1..]) {
+            Ok(output) => println!("{}", output),
+            Err(StringErr(e)) => eprintln!("{}", e),
+        }
+        return;
+    }
+
+    println!("Part 1: {}", part_1(parse_input(data.as_str())).unwrap());
+    println!("Part 2: {}", part_2(parse_input(data.as_str())).unwrap());
 }
 
-lazy_static! {
-    static ref SURROUNDING_DELTAS: Vec<(i32, i32)> = vec![
-        (0, -1),
-        (0, 1),
-        (-1, 0),
-        (1, 0),
-        (-1, -1),
-        (1, 1),
-        (1, -1),
-        (-1, 1),
-    ];
+fn run_command(args: &[String]) -> Result<String, StringErr> {
+    match args {
+        [command, rule, rounds, path] if command == "life" => {
+            let rounds = rounds
+                .parse::<usize>()
+                .map_err(|_| StringErr(format!("invalid number of rounds `{}`", rounds)))?;
+            let pattern =
+                fs::read_to_string(path).map_err(|e| StringErr(format!("{}: {}", path, e)))?;
+
+            let mut automaton = Automaton::new(&parse_input(&pattern), Rules::parse(rule)?)?;
+            let outcome = automaton.run(rounds);
+            Ok(format!("{}{}", automaton, outcome))
+        }
+        [command, rest @ ..] if command == "outcome" => {
+            let rules = match rest.first().map(|part| part.as_str()) {
+                None | Some("1") => Rules::adjacent_seats(),
+                Some("2") => Rules::visible_seats(),
+                Some(part) => return Err(StringErr(format!("unknown part `{}`", part))),
+            };
+            let threads = match rest.get(1) {
+                Some(threads) => threads
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&threads| threads > 0)
+                    .ok_or_else(|| StringErr(format!("invalid thread count `{}`", threads)))?,
+                None => 1,
+            };
+            let data = fs::read_to_string("inputs/day_11.txt")
+                .map_err(|e| StringErr(format!("inputs/day_11.txt: {}", e)))?;
+            Ok(Automaton::new(&parse_input(&data), rules)?
+                .with_threads(threads)
+                .run(MAX_ROUNDS)
+                .to_string())
+        }
+        _ => Err(StringErr(
+            "expected `life <rule> <rounds> <path>` or `outcome [part] [threads]`".to_owned(),
+        )),
+    }
 }
 
-fn main() {
-    let data = fs::read_to_string("inputs/day_11.txt").expect("Unable to read file");
+// text and ndjson frames are written out as each round runs
+fn frames_command<W: Write>(args: &[String], mut output: W) -> Result<(), StringErr> {
+    let (format, rest) = args.split_first().ok_or_else(|| {
+        StringErr("expected `frames <text|ndjson|ppm> [part] [directory]`".to_owned())
+    })?;
+    let rules = match rest.first().map(|part| part.as_str()) {
+        None | Some("1") => Rules::adjacent_seats(),
+        Some("2") => Rules::visible_seats(),
+        Some(part) => return Err(StringErr(format!("unknown part `{}`", part))),
+    };
+    let data = fs::read_to_string("inputs/day_11.txt")
+        .map_err(|e| StringErr(format!("inputs/day_11.txt: {}", e)))?;
+    let mut automaton = Automaton::new(&parse_input(&data), rules)?;
+    let mut frames = automaton.frames(MAX_FRAMES);
+    let write_error = |e: io::Error| StringErr(format!("unable to write frame: {}", e));
+
+    match format.as_str() {
+        "text" => {
+            for frame in frames.by_ref() {
+                writeln!(output, "{}", frame.to_text()).map_err(write_error)?;
+            }
+        }
+        "ndjson" => {
+            for frame in frames.by_ref() {
+                writeln!(output, "{}", frame.to_json()).map_err(write_error)?;
+            }
+        }
+        "ppm" => {
+            let directory = rest.get(1).map_or("frames", |d| d.as_str());
+            fs::create_dir_all(directory)
+                .map_err(|e| StringErr(format!("{}: {}", directory, e)))?;
+            let mut count = 0;
+            for frame in frames.by_ref() {
+                let path = format!("{}/round_{:04}.ppm", directory, frame.round);
+                fs::write(&path, frame.to_ppm(&rules, PPM_SCALE))
+                    .map_err(|e| StringErr(format!("{}: {}", path, e)))?;
+                count += 1;
+            }
+            writeln!(output, "wrote {} frames to {}", count, directory).map_err(write_error)?;
+        }
+        _ => return Err(StringErr(format!("unknown format `{}`", format))),
+    }
 
-    println!("Part 1: {}", part_1(parse_input(data.as_str())));
-    println!("Part 2: {}", part_2(parse_input(data.as_str())));
+    if frames.truncated() {
+        return Err(StringErr(format!(
+            "stopped after {} rounds before the seats settled",
+            MAX_FRAMES
+        )));
+    }
+    Ok(())
 }
 
 fn parse_input(input: &str) -> Vec<Vec<char>> {
@@ -37,6 +150,583 @@ fn parse_input(input: &str) -> Vec<Vec<char>> {
         .collect()
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Neighbourhood {
+    // the eight cells touching a cell
+    Adjacent,
+    // the first cell that isn't floor in each of the eight directions
+    LineOfSight,
+}
+
+// the states a cell can be in and when it switches between them. a dead cell comes
+// alive when its number of alive neighbours is in `born`, an alive one stays alive
+// when it is in `survive`, and floor never changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rules {
+    neighbourhood: Neighbourhood,
+    alive: char,
+    dead: char,
+    floor: Option<char>,
+    born: [bool; 9],
+    survive: [bool; 9],
+}
+
+fn neighbour_counts(counts: &[usize]) -> [bool; 9] {
+    let mut set = [false; 9];
+    counts.iter().for_each(|&count| set[count] = true);
+    set
+}
+
+impl Rules {
+    // an empty seat with no occupied seats around it fills up and an occupied one
+    // empties once `tolerance` of the seats around it are occupied
+    fn seating(neighbourhood: Neighbourhood, tolerance: usize) -> Rules {
+        Rules {
+            neighbourhood,
+            alive: '#',
+            dead: 'L',
+            floor: Some('.'),
+            born: neighbour_counts(&[0]),
+            survive: neighbour_counts(&(0..tolerance).collect::<Vec<usize>>()),
+        }
+    }
+
+    fn adjacent_seats() -> Rules {
+        Rules::seating(Neighbourhood::Adjacent, 4)
+    }
+
+    fn visible_seats() -> Rules {
+        Rules::seating(Neighbourhood::LineOfSight, 5)
+    }
+
+    // a Game of Life rule string like `B3/S23` on a grid of `#` and `.`
+    fn parse(rule: &str) -> Result<Rules, StringErr> {
+        let invalid = || StringErr(format!("invalid rule `{}`", rule));
+        let counts = |part: &str, prefix: char| -> Result<[bool; 9], StringErr> {
+            let digits = part
+                .strip_prefix(prefix)
+                .or_else(|| part.strip_prefix(prefix.to_ascii_lowercase()))
+                .ok_or_else(invalid)?;
+            let counts = digits
+                .chars()
+                .map(|c| c.to_digit(10).filter(|&d| d <= 8).map(|d| d as usize))
+                .collect::<Option<Vec<usize>>>()
+                .ok_or_else(invalid)?;
+            Ok(neighbour_counts(&counts))
+        };
+
+        let (born, survive) = match rule.split('/').collect::<Vec<&str>>().as_slice() {
+            [born, survive] => (counts(born, 'B')?, counts(survive, 'S')?),
+            _ => return Err(invalid()),
+        };
+
+        Ok(Rules {
+            neighbourhood: Neighbourhood::Adjacent,
+            alive: '#',
+            dead: '.',
+            floor: None,
+            born,
+            survive,
+        })
+    }
+}
+
+impl fmt::Display for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = |set: &[bool; 9]| {
+            (0..9)
+                .filter(|&count| set[count])
+                .map(|count| count.to_string())
+                .collect::<String>()
+        };
+        write!(f, "B{}/S{}", digits(&self.born), digits(&self.survive))
+    }
+}
+
+impl Rules {
+    fn next_state(&self, cell: char, alive_neighbours: usize) -> char {
+        if Some(cell) == self.floor {
+            return cell;
+        }
+
+        let alive = if cell == self.alive {
+            self.survive[alive_neighbours]
+        } else {
+            self.born[alive_neighbours]
+        };
+        if alive {
+            self.alive
+        } else {
+            self.dead
+        }
+    }
+}
+
+// every cell's neighbours worked out once up front, which is fine for line of
+// sight too as floor never changes
+struct Neighbours {
+    // cell i's neighbours are `list[starts[i]..starts[i + 1]]`
+    starts: Vec<usize>,
+    list: Vec<usize>,
+}
+
+impl Neighbours {
+    fn new(cells: &[char], width: usize, rules: &Rules) -> Neighbours {
+        let height = cells.len().checked_div(width).unwrap_or(0);
+        let inside = |x: i32, y: i32| x >= 0 && y >= 0 && x < width as i32 && y < height as i32;
+        let mut starts = vec![0];
+        let mut list = Vec::new();
+
+        for index in 0..cells.len() {
+            let (x, y) = ((index % width) as i32, (index / width) as i32);
+            for &(dx, dy) in SURROUNDING_DELTAS.iter() {
+                let (mut nx, mut ny) = (x + dx, y + dy);
+                while inside(nx, ny) {
+                    let neighbour = ny as usize * width + nx as usize;
+                    if rules.neighbourhood == Neighbourhood::Adjacent
+                        || Some(cells[neighbour]) != rules.floor
+                    {
+                        list.push(neighbour);
+                        break;
+                    }
+                    nx += dx;
+                    ny += dy;
+                }
+            }
+            starts.push(list.len());
+        }
+
+        Neighbours { starts, list }
+    }
+
+    fn get(&self, index: usize) -> &[usize] {
+        &self.list[self.starts[index]..self.starts[index + 1]]
+    }
+}
+
+// works out cells `start..start + band.len()` of the next round into `band` and
+// returns whether any of them changed
+fn update_band(
+    rules: &Rules,
+    neighbours: &Neighbours,
+    cells: &[char],
+    start: usize,
+    band: &mut [char],
+) -> bool {
+    let mut changed = false;
+    for (offset, cell) in band.iter_mut().enumerate() {
+        let index = start + offset;
+        let alive = neighbours
+            .get(index)
+            .iter()
+            .filter(|&&n| cells[n] == rules.alive)
+            .count();
+        *cell = rules.next_state(cells[index], alive);
+        changed |= *cell != cells[index];
+    }
+    changed
+}
+
+// the last round and the band of the next one for a worker to fill in
+type Job = (Arc<Vec<char>>, Vec<char>);
+
+// a thread per band of rows, started once and kept until the automaton is dropped
+// so a round costs a message to each worker rather than spawning threads
+struct Workers {
+    jobs: Vec<mpsc::Sender<Job>>,
+    // the worker's index, its band and whether any cell in it changed
+    results: mpsc::Receiver<(usize, Vec<char>, bool)>,
+    bands: Vec<Vec<char>>,
+    band_length: usize,
+}
+
+impl Workers {
+    fn new(
+        rules: Rules,
+        neighbours: &Arc<Neighbours>,
+        cells: usize,
+        band_length: usize,
+    ) -> Workers {
+        let (result_sender, results) = mpsc::channel();
+        let mut jobs = Vec::new();
+        let mut bands = Vec::new();
+
+        for (i, start) in (0..cells).step_by(band_length).enumerate() {
+            let (job_sender, job_receiver) = mpsc::channel::<Job>();
+            let result_sender = result_sender.clone();
+            let neighbours = Arc::clone(neighbours);
+            thread::spawn(move || {
+                for (cells, mut band) in job_receiver {
+                    let changed = update_band(&rules, &neighbours, &cells, start, &mut band);
+                    // let go of the last round before handing the band back
+                    drop(cells);
+                    if result_sender.send((i, band, changed)).is_err() {
+                        break;
+                    }
+                }
+            });
+            jobs.push(job_sender);
+            bands.push(vec![rules.dead; band_length.min(cells - start)]);
+        }
+
+        Workers {
+            jobs,
+            results,
+            bands,
+            band_length,
+        }
+    }
+
+    // runs a round from `cells` into `next` and returns whether any cell changed
+    fn update(&mut self, cells: Vec<char>, next: &mut [char]) -> (Vec<char>, bool) {
+        let cells = Arc::new(cells);
+        for (job, band) in self.jobs.iter().zip(self.bands.iter_mut()) {
+            job.send((Arc::clone(&cells), std::mem::take(band)))
+                .expect("Unable to run band");
+        }
+
+        let mut changed = false;
+        for _ in 0..self.jobs.len() {
+            let (i, band, band_changed) = self.results.recv().expect("Unable to run band");
+            let start = i * self.band_length;
+            next[start..start + band.len()].copy_from_slice(&band);
+            self.bands[i] = band;
+            changed |= band_changed;
+        }
+
+        let cells = Arc::try_unwrap(cells).expect("Workers should be done with the last round");
+        (cells, changed)
+    }
+}
+
+struct Automaton {
+    rules: Rules,
+    width: usize,
+    // row by row, with the next round written into `next` and then swapped in
+    cells: Vec<char>,
+    next: Vec<char>,
+    neighbours: Arc<Neighbours>,
+    // whether the last round changed any cell
+    changed: bool,
+    round: usize,
+    workers: Option<Workers>,
+}
+
+impl Automaton {
+    fn new(grid: &[Vec<char>], rules: Rules) -> Result<Automaton, StringErr> {
+        let width = grid.first().map_or(0, |row| row.len());
+        if let Some(y) = grid.iter().position(|row| row.len() != width) {
+            return Err(StringErr(format!(
+                "row {} isn't {} cells wide",
+                y + 1,
+                width
+            )));
+        }
+
+        let cells = grid.concat();
+        if let Some(cell) = cells
+            .iter()
+            .find(|&&c| c != rules.alive && c != rules.dead && Some(c) != rules.floor)
+        {
+            return Err(StringErr(format!("unknown cell `{}`", cell)));
+        }
+
+        Ok(Automaton {
+            rules,
+            width,
+            next: cells.clone(),
+            neighbours: Arc::new(Neighbours::new(&cells, width, &rules)),
+            cells,
+            changed: true,
+            round: 0,
+            workers: None,
+        })
+    }
+
+    // a new automaton counts as changed until its first round has run
+    fn is_stable(&self) -> bool {
+        !self.changed
+    }
+
+    // runs until a layout comes round again, spotting cycles of any period by
+    // keeping the hash of every layout with the round it was first seen in. only
+    // the starting layout is kept whole, and a hash seen before is confirmed by
+    // replaying from it so two layouts sharing a hash can't pass for a cycle.
+    fn run(&mut self, max_rounds: usize) -> Outcome {
+        let hash = |cells: &[char]| {
+            let mut hasher = DefaultHasher::new();
+            cells.hash(&mut hasher);
+            hasher.finish()
+        };
+        let (first_round, first_cells) = (self.round, self.cells.clone());
+        let mut seen = HashMap::new();
+        seen.insert(hash(&self.cells), self.round);
+        let limit = self.round + max_rounds;
+
+        loop {
+            if self.round >= limit {
+                return Outcome::RoundLimit {
+                    round: self.round,
+                    occupied: self.alive_count(),
+                };
+            }
+
+            self.step();
+            if self.is_stable() {
+                return Outcome::Stabilized {
+                    round: self.round - 1,
+                    occupied: self.alive_count(),
+                };
+            }
+            let key = hash(&self.cells);
+            if let Some(&start) = seen.get(&key) {
+                if self.replay(&first_cells, start - first_round) == self.cells {
+                    return Outcome::Cycled {
+                        start,
+                        period: self.round - start,
+                        occupied: self.alive_count(),
+                    };
+                }
+            }
+            seen.entry(key).or_insert(self.round);
+        }
+    }
+
+    // the layout `rounds` rounds on from `cells`, worked out on the side
+    fn replay(&self, cells: &[char], rounds: usize) -> Vec<char> {
+        let mut cells = cells.to_vec();
+        let mut next = cells.clone();
+        for _ in 0..rounds {
+            update_band(&self.rules, &self.neighbours, &cells, 0, &mut next);
+            std::mem::swap(&mut cells, &mut next);
+        }
+        cells
+    }
+
+    // runs a round and returns whether any cell changed. with workers the rows are
+    // split into a band per worker, which only read the last round and each fill in
+    // their own part of the next one.
+    fn step(&mut self) -> bool {
+        let changed = match &mut self.workers {
+            Some(workers) => {
+                let (cells, changed) =
+                    workers.update(std::mem::take(&mut self.cells), &mut self.next);
+                self.cells = cells;
+                changed
+            }
+            None => update_band(
+                &self.rules,
+                &self.neighbours,
+                &self.cells,
+                0,
+                &mut self.next,
+            ),
+        };
+
+        std::mem::swap(&mut self.cells, &mut self.next);
+        self.changed = changed;
+        self.round += 1;
+        changed
+    }
+
+    // splits the rows into `threads` bands worked out side by side on threads that
+    // stay up for every round
+    fn with_threads(mut self, threads: usize) -> Automaton {
+        let height = self.cells.len().checked_div(self.width).unwrap_or(0);
+        self.workers = if threads > 1 && height > 1 {
+            let band_length = height.div_ceil(threads) * self.width;
+            Some(Workers::new(
+                self.rules,
+                &self.neighbours,
+                self.cells.len(),
+                band_length,
+            ))
+        } else {
+            None
+        };
+        self
+    }
+
+    fn alive_count(&self) -> usize {
+        self.cells
+            .iter()
+            .filter(|&&c| c == self.rules.alive)
+            .count()
+    }
+
+    // the current layout followed by one frame per round until nothing changes
+    // or `max_rounds` have run
+    fn frames(&mut self, max_rounds: usize) -> Frames<'_> {
+        Frames {
+            automaton: self,
+            max_rounds,
+            started: false,
+        }
+    }
+
+    fn frame(&self) -> Frame {
+        Frame {
+            round: self.round,
+            alive: self.alive_count(),
+            width: self.width,
+            cells: self.cells.clone(),
+        }
+    }
+}
+
+const MAX_FRAMES: usize = 1000;
+const MAX_ROUNDS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    // `round` is the last round that changed anything
+    Stabilized {
+        round: usize,
+        occupied: usize,
+    },
+    // the layout at round `start` comes back every `period` rounds
+    Cycled {
+        start: usize,
+        period: usize,
+        occupied: usize,
+    },
+    RoundLimit {
+        round: usize,
+        occupied: usize,
+    },
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Stabilized { round, occupied } => write!(
+                f,
+                "stabilized after {} rounds with {} occupied",
+                round, occupied
+            ),
+            Outcome::Cycled {
+                start,
+                period,
+                occupied,
+            } => write!(
+                f,
+                "cycled every {} rounds from round {}, {} occupied when it repeated",
+                period, start, occupied
+            ),
+            Outcome::RoundLimit { round, occupied } => {
+                write!(f, "stopped at round {} with {} occupied", round, occupied)
+            }
+        }
+    }
+}
+
+const PPM_SCALE: usize = 4;
+
+struct Frames<'a> {
+    automaton: &'a mut Automaton,
+    max_rounds: usize,
+    started: bool,
+}
+
+impl<'a> Frames<'a> {
+    // whether the frames ran out at `max_rounds` rather than once nothing changed
+    fn truncated(&self) -> bool {
+        self.max_rounds == 0 && !self.automaton.is_stable()
+    }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        if !self.started {
+            self.started = true;
+            return Some(self.automaton.frame());
+        }
+        if self.max_rounds == 0 || !self.automaton.step() {
+            return None;
+        }
+        self.max_rounds -= 1;
+        Some(self.automaton.frame())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Frame {
+    round: usize,
+    alive: usize,
+    width: usize,
+    cells: Vec<char>,
+}
+
+impl Frame {
+    fn rows(&self) -> Vec<String> {
+        self.cells
+            .chunks(self.width.max(1))
+            .map(|row| row.iter().collect())
+            .collect()
+    }
+
+    fn to_text(&self) -> String {
+        format!(
+            "round {} occupied {}\n{}\n",
+            self.round,
+            self.alive,
+            self.rows().join("\n")
+        )
+    }
+
+    fn to_json(&self) -> String {
+        let rows = self
+            .rows()
+            .iter()
+            .map(|row| format!("\"{}\"", row.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect::<Vec<String>>();
+        format!(
+            "{{\"round\":{},\"occupied\":{},\"rows\":[{}]}}",
+            self.round,
+            self.alive,
+            rows.join(",")
+        )
+    }
+
+    // a binary ppm with each cell drawn as a `scale` by `scale` square
+    fn to_ppm(&self, rules: &Rules, scale: usize) -> Vec<u8> {
+        let height = self.cells.len().checked_div(self.width).unwrap_or(0);
+        let mut image =
+            format!("P6\n{} {}\n255\n", self.width * scale, height * scale).into_bytes();
+
+        for row in self.cells.chunks(self.width.max(1)) {
+            let line = row
+                .iter()
+                .flat_map(|&cell| {
+                    let colour: [u8; 3] = if cell == rules.alive {
+                        [220, 60, 50]
+                    } else if cell == rules.dead {
+                        [70, 130, 210]
+                    } else {
+                        [25, 25, 25]
+                    };
+                    colour.repeat(scale)
+                })
+                .collect::<Vec<u8>>();
+            for _ in 0..scale {
+                image.extend(&line);
+            }
+        }
+
+        image
+    }
+}
+
+impl fmt::Display for Automaton {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.cells
+            .chunks(self.width.max(1))
+            .try_for_each(|row| writeln!(f, "{}", row.iter().collect::<String>()))
+    }
+}
+
 /*
 --- Day 11: Seating System ---
 
@@ -130,63 +820,8 @@ At this point, something interesting happens: the chaos stabilizes and further a
 
 Simulate your seating area by applying the seating rules repeatedly until no seats change state. How many seats end up occupied?
  */
-fn part_1(inputs: Vec<Vec<char>>) -> i32 {
-    let mut previous = inputs;
-    loop {
-        let next = simulate_round(&previous, &part_1_strategy);
-        if is_equal(&previous, &next) {
-            return get_occupied_seat_count(previous);
-        }
-        previous = next;
-    }
-}
-
-fn part_1_strategy(inputs: Vec<Vec<char>>, seat_value: char, x: usize, y: usize) -> Change {
-    if seat_value == 'L' {
-        let unoccupied = SURROUNDING_DELTAS
-            .iter()
-            .map(|&(dx, dy)| {
-                let new_y = (y as i32 + dy) as usize;
-                let new_x = (x as i32 + dx) as usize;
-
-                if inputs.get(new_y).and_then(|col| col.get(new_x)).is_none() {
-                    return 'L';
-                }
-
-                inputs[new_y][new_x]
-            })
-            .all(|val| val == 'L' || val == '.');
-
-        return if unoccupied {
-            Change::FILL
-        } else {
-            Change::NONE
-        };
-    }
-
-    if seat_value == '#' {
-        let occupied_count: i32 = SURROUNDING_DELTAS
-            .iter()
-            .map(|&(dx, dy)| {
-                let new_y = (y as i32 + dy) as usize;
-                let new_x = (x as i32 + dx) as usize;
-
-                if inputs.get(new_y).and_then(|col| col.get(new_x)).is_none() {
-                    return 0;
-                }
-
-                return if inputs[new_y][new_x] == '#' { 1 } else { 0 };
-            })
-            .sum();
-
-        return if occupied_count >= 4 {
-            Change::ABANDON
-        } else {
-            Change::NONE
-        };
-    }
-
-    Change::NONE
+fn part_1(inputs: Vec<Vec<char>>) -> Result<usize, StringErr> {
+    settle(&inputs, Rules::adjacent_seats())
 }
 
 /*
@@ -307,123 +942,49 @@ Again, at this point, people stop shifting around and the seating area reaches e
 
 Given the new visibility method and the rule change for occupied seats becoming empty, once equilibrium is reached, how many seats end up occupied?
  */
-fn part_2(inputs: Vec<Vec<char>>) -> i32 {
-    let mut previous = inputs;
-    loop {
-        let next = simulate_round(&previous, &part_2_strategy);
-        if is_equal(&previous, &next) {
-            return get_occupied_seat_count(previous);
-        }
-        previous = next;
-    }
+fn part_2(inputs: Vec<Vec<char>>) -> Result<usize, StringErr> {
+    settle(&inputs, Rules::visible_seats())
 }
 
-fn part_2_strategy(inputs: Vec<Vec<char>>, seat_value: char, x: usize, y: usize) -> Change {
-    if seat_value == 'L' {
-        let unoccupied = SURROUNDING_DELTAS
-            .iter()
-            .map(|&(dx, dy)| {
-                let res = get_nearest_seat(&inputs, x, y, dx, dy);
-                if res.is_none() {
-                    return 'L';
-                }
-
-                return res.unwrap();
-            })
-            .all(|val| val == 'L');
-
-        return if unoccupied {
-            Change::FILL
-        } else {
-            Change::NONE
-        };
+fn settle(inputs: &[Vec<char>], rules: Rules) -> Result<usize, StringErr> {
+    match Automaton::new(inputs, rules)?.run(MAX_ROUNDS) {
+        Outcome::Stabilized { occupied, .. } => Ok(occupied),
+        outcome => Err(StringErr(format!("the seats never settle: {}", outcome))),
     }
+}
 
-    if seat_value == '#' {
-        let occupied_count: i32 = SURROUNDING_DELTAS
-            .iter()
-            .map(|&(dx, dy)| {
-                let res = get_nearest_seat(&inputs, x, y, dx, dy);
-                if res.is_none() {
-                    return 0;
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
 
-                return if res.unwrap() == '#' { 1 } else { 0 };
-            })
-            .sum();
+    // counts the bytes each thread has allocated and not yet freed, so a test can
+    // see how much memory a run holds on to at its peak
+    struct CountingAllocator;
 
-        return if occupied_count >= 5 {
-            Change::ABANDON
-        } else {
-            Change::NONE
-        };
+    thread_local! {
+        static LIVE: Cell<usize> = const { Cell::new(0) };
+        static PEAK: Cell<usize> = const { Cell::new(0) };
     }
 
-    Change::NONE
-}
-
-fn get_nearest_seat(inputs: &Vec<Vec<char>>, x: usize, y: usize, dx: i32, dy: i32) -> Option<char> {
-    let mut cdx = dx;
-    let mut cdy = dy;
-
-    loop {
-        let new_y = (y as i32 + cdy) as usize;
-        let new_x = (x as i32 + cdx) as usize;
-
-        if inputs.get(new_y).and_then(|col| col.get(new_x)).is_none() {
-            return None;
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = LIVE.try_with(|live| {
+                live.set(live.get() + layout.size());
+                let _ = PEAK.try_with(|peak| peak.set(peak.get().max(live.get())));
+            });
+            System.alloc(layout)
         }
 
-        if inputs[new_y][new_x] != '.' {
-            return Some(inputs[new_y][new_x]);
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let _ = LIVE.try_with(|live| live.set(live.get().saturating_sub(layout.size())));
+            System.dealloc(ptr, layout)
         }
-
-        cdx += dx;
-        cdy += dy;
     }
-}
 
-fn simulate_round(
-    inputs: &Vec<Vec<char>>,
-    f: &dyn Fn(Vec<Vec<char>>, char, usize, usize) -> Change,
-) -> Vec<Vec<char>> {
-    inputs
-        .iter()
-        .enumerate()
-        .map(|(y, val)| {
-            val.iter()
-                .enumerate()
-                .map(|(x, value)| match f(inputs.clone(), *value, x, y) {
-                    Change::FILL => '#',
-                    Change::ABANDON => 'L',
-                    Change::NONE => *value,
-                })
-                .collect::<Vec<char>>()
-        })
-        .collect::<Vec<Vec<char>>>()
-}
-
-fn is_equal(a: &Vec<Vec<char>>, b: &Vec<Vec<char>>) -> bool {
-    let matching = a
-        .iter()
-        .zip(b.iter())
-        .filter(|&(a, b)| a.into_iter().collect::<String>() == b.into_iter().collect::<String>())
-        .count();
-    matching == a.len() && matching == b.len()
-}
-
-fn get_occupied_seat_count(inputs: Vec<Vec<char>>) -> i32 {
-    inputs
-        .iter()
-        .map(|val| val.into_iter().collect::<String>())
-        .join("")
-        .chars()
-        .map(|c| return if c == '#' { 1 } else { 0 })
-        .sum::<i32>()
-}
-
-mod tests {
-    use super::*;
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
 
     #[test]
     fn test_part_1_first_round() {
@@ -449,10 +1010,10 @@ L.LLLLL.LL";
 #.######.#
 #.#####.##";
 
-        let next_round = simulate_round(&parse_input(input), &part_1_strategy);
-        let output_chars = parse_input(output);
+        let mut automaton = Automaton::new(&parse_input(input), Rules::adjacent_seats()).unwrap();
 
-        assert_eq!(is_equal(&next_round, &output_chars), true)
+        assert!(automaton.step());
+        assert_eq!(automaton.to_string(), format!("{}\n", output))
     }
 
     #[test]
@@ -468,10 +1029,17 @@ L.#.L..#..
 #.LLLLLL.L
 #.#L#L#.##";
 
-        let next_round = simulate_round(&parse_input(repeating_input_output), &part_1_strategy);
-        let output_chars = parse_input(repeating_input_output);
-
-        assert_eq!(is_equal(&next_round, &output_chars), true)
+        let mut automaton = Automaton::new(
+            &parse_input(repeating_input_output),
+            Rules::adjacent_seats(),
+        )
+        .unwrap();
+
+        assert!(!automaton.step());
+        assert_eq!(
+            automaton.to_string(),
+            format!("{}\n", repeating_input_output)
+        )
     }
 
     #[test]
@@ -487,7 +1055,7 @@ LLLLLLLLLL
 L.LLLLLL.L
 L.LLLLL.LL";
 
-        assert_eq!(part_1(parse_input(input)), 37)
+        assert_eq!(part_1(parse_input(input)).unwrap(), 37)
     }
 
     #[test]
@@ -503,6 +1071,270 @@ L.LLLLL.LL";
     L.LLLLLL.L
     L.LLLLL.LL";
 
-        assert_eq!(part_2(parse_input(input)), 26)
+        assert_eq!(part_2(parse_input(input)).unwrap(), 26)
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let input = ".##.##.
+#.#.#.#
+##...##
+...L...
+##...##
+#.#.#.#
+.##.##.";
+        let automaton = Automaton::new(&parse_input(input), Rules::visible_seats()).unwrap();
+        assert!(automaton.neighbours.get(24).is_empty());
+        assert_eq!(automaton.neighbours.get(0), &[7, 1, 24]);
+
+        let input = ".............
+.L.L.#.#.#.#.
+.............";
+        let automaton = Automaton::new(&parse_input(input), Rules::visible_seats()).unwrap();
+        assert_eq!(automaton.neighbours.get(14), &[16]);
+    }
+
+    #[test]
+    fn test_life_rules() {
+        let rules = Rules::parse("B3/S23").unwrap();
+        assert_eq!(rules.to_string(), "B3/S23");
+        assert_eq!(Rules::adjacent_seats().to_string(), "B0/S0123");
+        assert_eq!(Rules::parse("b36/s23").unwrap().to_string(), "B36/S23");
+        assert!(Rules::parse("B9/S23").is_err());
+        assert!(Rules::parse("S23/B3").is_err());
+
+        // a blinker flips between a row and a column
+        let mut automaton =
+            Automaton::new(&parse_input(".....\n..#..\n..#..\n..#..\n....."), rules).unwrap();
+        assert!(automaton.step());
+        assert_eq!(automaton.to_string(), ".....\n.....\n.###.\n.....\n.....\n");
+        assert!(automaton.step());
+        assert_eq!(automaton.alive_count(), 3);
+
+        assert!(Automaton::new(&parse_input("#L"), rules).is_err());
+        assert!(Automaton::new(&parse_input("##\n#"), rules).is_err());
+    }
+
+    #[test]
+    fn test_frames() {
+        let input = "L.LL.LL.LL
+LLLLLLL.LL
+L.L.L..L..
+LLLL.LL.LL
+L.LL.LL.LL
+L.LLLLL.LL
+..L.L.....
+LLLLLLLLLL
+L.LLLLLL.L
+L.LLLLL.LL";
+        let mut automaton = Automaton::new(&parse_input(input), Rules::adjacent_seats()).unwrap();
+        let frames = automaton.frames(100).collect::<Vec<Frame>>();
+
+        // the layout, then five rounds of changes before it settles
+        assert_eq!(
+            frames
+                .iter()
+                .map(|f| (f.round, f.alive))
+                .collect::<Vec<(usize, usize)>>(),
+            vec![(0, 0), (1, 71), (2, 20), (3, 51), (4, 30), (5, 37)]
+        );
+        assert_eq!(
+            frames[1].to_text().lines().take(2).collect::<Vec<&str>>(),
+            vec!["round 1 occupied 71", "#.##.##.##"]
+        );
+        assert!(frames[5]
+            .to_json()
+            .starts_with("{\"round\":5,\"occupied\":37,\"rows\":[\"#.#L.L#.##\",\"#LLL#LL.L#\","));
+
+        let mut automaton = Automaton::new(&parse_input(input), Rules::adjacent_seats()).unwrap();
+        let mut frames = automaton.frames(2);
+        assert_eq!(frames.by_ref().count(), 3);
+        assert!(frames.truncated());
+        assert_eq!(automaton.round, 2);
+
+        // carries on from round 2 until it settles
+        let mut frames = automaton.frames(100);
+        assert_eq!(frames.by_ref().count(), 4);
+        assert!(!frames.truncated());
+    }
+
+    #[test]
+    fn test_ppm() {
+        let mut automaton =
+            Automaton::new(&parse_input("#.\nLL"), Rules::adjacent_seats()).unwrap();
+        let image = automaton
+            .frames(0)
+            .next()
+            .unwrap()
+            .to_ppm(&Rules::adjacent_seats(), 2);
+        let header = b"P6\n4 4\n255\n";
+
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 4 * 4 * 3);
+        assert_eq!(
+            &image[header.len()..header.len() + 12],
+            &[220, 60, 50, 220, 60, 50, 25, 25, 25, 25, 25, 25]
+        );
+        assert_eq!(&image[image.len() - 3..], &[70, 130, 210]);
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let input = "L.LL.LL.LL
+LLLLLLL.LL
+L.L.L..L..
+LLLL.LL.LL
+L.LL.LL.LL
+L.LLLLL.LL
+..L.L.....
+LLLLLLLLLL
+L.LLLLLL.L
+L.LLLLL.LL";
+        let outcome = |rules: Rules, rounds: usize| {
+            Automaton::new(&parse_input(input), rules)
+                .unwrap()
+                .run(rounds)
+        };
+
+        assert_eq!(
+            outcome(Rules::adjacent_seats(), 100),
+            Outcome::Stabilized {
+                round: 5,
+                occupied: 37
+            }
+        );
+        assert_eq!(
+            outcome(Rules::adjacent_seats(), 3),
+            Outcome::RoundLimit {
+                round: 3,
+                occupied: 51
+            }
+        );
+
+        // a blinker comes back every other round
+        let blinker = parse_input(".....\n..#..\n..#..\n..#..\n.....");
+        let mut automaton = Automaton::new(&blinker, Rules::parse("B3/S23").unwrap()).unwrap();
+        assert_eq!(
+            automaton.run(100),
+            Outcome::Cycled {
+                start: 0,
+                period: 2,
+                occupied: 3
+            }
+        );
+
+        // every cell flips each round with B012345678/S
+        let mut automaton = Automaton::new(
+            &parse_input("#.\n.#"),
+            Rules::parse("B012345678/S").unwrap(),
+        )
+        .unwrap();
+        automaton.step();
+        assert_eq!(
+            automaton.run(100),
+            Outcome::Cycled {
+                start: 1,
+                period: 2,
+                occupied: 2
+            }
+        );
+        assert_eq!(automaton.round, 3);
+    }
+
+    #[test]
+    fn test_long_runs_keep_one_layout() {
+        // a row of gliders heading down and to the right, which keeps changing
+        // for hundreds of rounds without a layout coming round again
+        let (width, height) = (120, 120);
+        let mut grid = vec![vec!['.'; width]; height];
+        for x in (0..width - 8).step_by(8) {
+            for &(dx, dy) in &[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+                grid[dy + 1][x + dx] = '#';
+            }
+        }
+        let mut automaton = Automaton::new(&grid, Rules::parse("B3/S23").unwrap()).unwrap();
+
+        let before = LIVE.with(Cell::get);
+        PEAK.with(|peak| peak.set(before));
+        assert!(matches!(
+            automaton.run(300),
+            Outcome::RoundLimit { round: 300, .. }
+        ));
+
+        // holding on to every layout would take 300 of them
+        let layout = width * height * std::mem::size_of::<char>();
+        assert!(PEAK.with(Cell::get) - before < 8 * layout);
+    }
+
+    #[test]
+    fn test_seats_that_never_settle() {
+        // both seats fill, then with a tolerance of one both empty again
+        let rules = Rules::seating(Neighbourhood::Adjacent, 1);
+        assert_eq!(
+            Automaton::new(&parse_input("LL"), rules).unwrap().run(10),
+            Outcome::Cycled {
+                start: 0,
+                period: 2,
+                occupied: 0
+            }
+        );
+        assert!(settle(&parse_input("LL"), rules).is_err());
+    }
+
+    #[test]
+    fn test_threads_match_single_threaded() {
+        // a scrambled layout, with rows that don't split evenly between the threads
+        let mut seed = 11u32;
+        let layout = (0..37)
+            .map(|_| {
+                (0..23)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                        ['L', 'L', '#', '.'][(seed >> 16) as usize % 4]
+                    })
+                    .collect::<Vec<char>>()
+            })
+            .collect::<Vec<Vec<char>>>();
+        let life = layout
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&c| if c == '#' { '#' } else { '.' })
+                    .collect()
+            })
+            .collect::<Vec<Vec<char>>>();
+
+        for (grid, rules) in &[
+            (&layout, Rules::adjacent_seats()),
+            (&layout, Rules::visible_seats()),
+            (&life, Rules::parse("B3/S23").unwrap()),
+        ] {
+            for &threads in &[2, 3, 8, 64] {
+                let mut single = Automaton::new(grid, *rules).unwrap();
+                let mut parallel = Automaton::new(grid, *rules).unwrap().with_threads(threads);
+                for _ in 0..30 {
+                    assert_eq!(parallel.step(), single.step());
+                    assert_eq!(parallel.cells, single.cells);
+                }
+            }
+        }
+
+        // 37 rows in bands of 5 over 8 workers, which then run every round
+        let parallel = Automaton::new(&layout, Rules::adjacent_seats())
+            .unwrap()
+            .with_threads(8);
+        assert_eq!(parallel.workers.as_ref().map(|w| w.jobs.len()), Some(8));
+        assert!(run_command(&["outcome", "1", "0"].map(String::from)).is_err());
+
+        let input = "L.LL.LL.LL\nLLLLLLL.LL\nL.L.L..L..\nLLLL.LL.LL\nL.LL.LL.LL";
+        assert_eq!(
+            Automaton::new(&parse_input(input), Rules::visible_seats())
+                .unwrap()
+                .with_threads(4)
+                .run(100),
+            Automaton::new(&parse_input(input), Rules::visible_seats())
+                .unwrap()
+                .run(100)
+        );
     }
 }
@@ -1,6 +1,10 @@
 use lazy_static::lazy_static;
+use std::env;
 use std::fs;
 
+#[derive(Debug)]
+struct StringErr(String);
+
 lazy_static! {
     static ref DIRECTIONS: Vec<(i32, i32)> = vec![(1, 0), (0, -1), (-1, 0), (0, 1)];
 }
@@ -8,8 +12,37 @@ lazy_static! {
 fn main() {
     let data = fs::read_to_string("inputs/day_12.txt").expect("Unable to read file");
 
-    println!("Part 1: {}", part_1(parse_input(data.as_str())));
-    println!("Part 2: {}", part_2(parse_input(data.as_str())));
+    // `day_12 trace <1|2> <csv|svg>` prints every step of the route for either part
+    let args = env::args().collect::<Vec<String>>();
+    if args.len() > 1 {
+        match run_command(&args[1..], &data) {
+            Ok(output) => print!("{}", output),
+            Err(StringErr(e)) => eprintln!("{}", e),
+        }
+        return;
+    }
+
+    println!("Part 1: {}", part_1(parse_input(data.as_str())).unwrap());
+    println!("Part 2: {}", part_2(parse_input(data.as_str())).unwrap());
+}
+
+fn run_command(args: &[String], data: &str) -> Result<String, StringErr> {
+    let (part, format) = match args {
+        [command, part, format] if command == "trace" => (part.as_str(), format.as_str()),
+        _ => return Err(StringErr("expected `trace <1|2> <csv|svg>`".to_owned())),
+    };
+    let mode = match part {
+        "1" => Mode::Ship,
+        "2" => Mode::Waypoint,
+        _ => return Err(StringErr(format!("unknown part `{}`", part))),
+    };
+    let navigator = Navigator::run(mode, &parse_input(data))?;
+
+    match format {
+        "csv" => Ok(navigator.to_csv()),
+        "svg" => Ok(navigator.to_svg()),
+        _ => Err(StringErr(format!("unknown format `{}`", format))),
+    }
 }
 
 fn parse_input(input: &str) -> Vec<(char, i32)> {
@@ -66,23 +99,8 @@ At the end of these instructions, the ship's Manhattan distance (sum of the abso
 
 Figure out where the navigation instructions lead. What is the Manhattan distance between that location and the ship's starting position?
  */
-fn part_1(instructions: Vec<(char, i32)>) -> i32 {
-    let mut coord = (0, 0);
-    let mut direction_idx: i32 = 0;
-    instructions.iter().for_each(|(ins, val)| {
-        let (dx, dy) = DIRECTIONS[direction_idx as usize];
-        match ins {
-            'N' => coord = (coord.0, coord.1 + val),
-            'S' => coord = (coord.0, coord.1 - val),
-            'E' => coord = (coord.0 + val, coord.1),
-            'W' => coord = (coord.0 - val, coord.1),
-            'F' => coord = (coord.0 + (dx * val), coord.1 + (dy * val)),
-            'L' => direction_idx = (4 + (direction_idx - (val / 90))) % 4,
-            'R' => direction_idx = (direction_idx + (val / 90)) % 4,
-            _ => panic!("unknown instruction"),
-        }
-    });
-    coord.0.abs() + coord.1.abs()
+fn part_1(instructions: Vec<(char, i32)>) -> Result<i32, StringErr> {
+    Ok(Navigator::run(Mode::Ship, &instructions)?.distance())
 }
 
 /*
@@ -114,34 +132,223 @@ After these operations, the ship's Manhattan distance from its starting position
 
 Figure out where the navigation instructions actually lead. What is the Manhattan distance between that location and the ship's starting position?
  */
-fn part_2(instructions: Vec<(char, i32)>) -> i32 {
-    let mut wp = (10, 1);
-    let mut coord = (0, 0);
-    instructions.iter().for_each(|(ins, val)| match ins {
-        'N' => wp = (wp.0, wp.1 + val),
-        'S' => wp = (wp.0, wp.1 - val),
-        'E' => wp = (wp.0 + val, wp.1),
-        'W' => wp = (wp.0 - val, wp.1),
-        'F' => coord = (coord.0 + (wp.0 * val), coord.1 + (wp.1 * val)),
-        'L' => wp = rotate_waypoint(wp, -val / 90),
-        'R' => wp = rotate_waypoint(wp, val / 90),
-        _ => panic!("unknown instruction"),
-    });
-    coord.0.abs() + coord.1.abs()
+fn part_2(instructions: Vec<(char, i32)>) -> Result<i32, StringErr> {
+    Ok(Navigator::run(Mode::Waypoint, &instructions)?.distance())
 }
 
 fn rotate_waypoint(wp: (i32, i32), turn: i32) -> (i32, i32) {
-    let mut tmp_wp = wp.clone();
+    let mut tmp_wp = wp;
     for _ in 0..turn.abs() {
-        match turn / turn.abs() {
-            1 => tmp_wp = (tmp_wp.1, tmp_wp.0 * -1),
-            -1 => tmp_wp = (tmp_wp.1 * -1, tmp_wp.0),
+        match turn.signum() {
+            1 => tmp_wp = (tmp_wp.1, -tmp_wp.0),
+            -1 => tmp_wp = (-tmp_wp.1, tmp_wp.0),
             _ => panic!("invalid"),
         }
     }
     tmp_wp
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    // N, S, E and W move the ship, as in part 1
+    Ship,
+    // N, S, E and W move the waypoint, as in part 2
+    Waypoint,
+}
+
+const HEADINGS: [char; 4] = ['E', 'S', 'W', 'N'];
+
+// where the ship is after an instruction, `None` being the starting point. the
+// ship only has a heading when it steers itself and a waypoint when it follows one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Step {
+    instruction: Option<(char, i32)>,
+    position: (i32, i32),
+    heading: Option<char>,
+    waypoint: Option<(i32, i32)>,
+}
+
+struct Navigator {
+    mode: Mode,
+    position: (i32, i32),
+    direction_idx: i32,
+    waypoint: (i32, i32),
+    trace: Vec<Step>,
+}
+
+impl Navigator {
+    fn new(mode: Mode) -> Self {
+        let mut navigator = Self {
+            mode,
+            position: (0, 0),
+            direction_idx: 0,
+            waypoint: (10, 1),
+            trace: Vec::new(),
+        };
+        navigator.record(None);
+        navigator
+    }
+
+    fn run(mode: Mode, instructions: &[(char, i32)]) -> Result<Navigator, StringErr> {
+        let mut navigator = Navigator::new(mode);
+        for (i, &(ins, val)) in instructions.iter().enumerate() {
+            navigator
+                .apply(ins, val)
+                .map_err(|StringErr(e)| StringErr(format!("instruction {}: {}", i + 1, e)))?;
+        }
+        Ok(navigator)
+    }
+
+    fn record(&mut self, instruction: Option<(char, i32)>) {
+        self.trace.push(Step {
+            instruction,
+            position: self.position,
+            heading: match self.mode {
+                Mode::Ship => Some(HEADINGS[self.direction_idx as usize]),
+                Mode::Waypoint => None,
+            },
+            waypoint: match self.mode {
+                Mode::Ship => None,
+                Mode::Waypoint => Some(self.waypoint),
+            },
+        });
+    }
+
+    fn apply(&mut self, ins: char, val: i32) -> Result<(), StringErr> {
+        if (ins == 'L' || ins == 'R') && val % 90 != 0 {
+            return Err(StringErr(format!("can't turn {} degrees", val)));
+        }
+
+        let moved = match self.mode {
+            Mode::Ship => &mut self.position,
+            Mode::Waypoint => &mut self.waypoint,
+        };
+        match ins {
+            'N' => moved.1 += val,
+            'S' => moved.1 -= val,
+            'E' => moved.0 += val,
+            'W' => moved.0 -= val,
+            'F' => {
+                let (dx, dy) = match self.mode {
+                    Mode::Ship => DIRECTIONS[self.direction_idx as usize],
+                    Mode::Waypoint => self.waypoint,
+                };
+                self.position = (self.position.0 + dx * val, self.position.1 + dy * val);
+            }
+            'L' | 'R' => {
+                let turn = if ins == 'L' { -val / 90 } else { val / 90 };
+                match self.mode {
+                    Mode::Ship => self.direction_idx = (self.direction_idx + turn).rem_euclid(4),
+                    Mode::Waypoint => self.waypoint = rotate_waypoint(self.waypoint, turn),
+                }
+            }
+            _ => return Err(StringErr(format!("unknown instruction `{}`", ins))),
+        }
+
+        self.record(Some((ins, val)));
+        Ok(())
+    }
+
+    fn distance(&self) -> i32 {
+        self.position.0.abs() + self.position.1.abs()
+    }
+
+    // x and y are east and north
+    fn to_csv(&self) -> String {
+        let mut csv = "step,instruction,x,y,heading,waypoint_x,waypoint_y\n".to_owned();
+        for (i, step) in self.trace.iter().enumerate() {
+            let instruction = step
+                .instruction
+                .map_or(String::new(), |(ins, val)| format!("{}{}", ins, val));
+            let heading = step.heading.map_or(String::new(), |c| c.to_string());
+            let waypoint = step
+                .waypoint
+                .map_or(",".to_owned(), |(x, y)| format!("{},{}", x, y));
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                i, instruction, step.position.0, step.position.1, heading, waypoint
+            ));
+        }
+        csv
+    }
+
+    // the route as a polyline with a green start and red end marker, and an arrow
+    // from each position to its waypoint. svg's y axis points down so north is
+    // flipped to point up.
+    fn to_svg(&self) -> String {
+        let points = self
+            .trace
+            .iter()
+            .flat_map(|step| {
+                let tip = step
+                    .waypoint
+                    .map(|(x, y)| (step.position.0 + x, step.position.1 + y));
+                std::iter::once(step.position).chain(tip)
+            })
+            .collect::<Vec<(i32, i32)>>();
+        let min_x = points.iter().map(|p| p.0).min().unwrap();
+        let max_x = points.iter().map(|p| p.0).max().unwrap();
+        let min_y = points.iter().map(|p| -p.1).min().unwrap();
+        let max_y = points.iter().map(|p| -p.1).max().unwrap();
+        let size = (max_x - min_x).max(max_y - min_y).max(1) as f64;
+        let padding = size / 20.0;
+        let stroke = size / 400.0;
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+            min_x as f64 - padding,
+            min_y as f64 - padding,
+            (max_x - min_x) as f64 + 2.0 * padding,
+            (max_y - min_y) as f64 + 2.0 * padding
+        );
+        svg.push_str(
+            "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"4\" markerHeight=\"4\" orient=\"auto\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"grey\"/></marker></defs>\n",
+        );
+
+        for step in &self.trace {
+            if let Some((x, y)) = step.waypoint {
+                let (px, py) = step.position;
+                svg.push_str(&format!(
+                    "<line class=\"waypoint\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"grey\" stroke-width=\"{}\" marker-end=\"url(#arrow)\"/>\n",
+                    px,
+                    -py,
+                    px + x,
+                    -(py + y),
+                    stroke / 2.0
+                ));
+            }
+        }
+
+        let route = self
+            .trace
+            .iter()
+            .map(|step| format!("{},{}", step.position.0, -step.position.1))
+            .collect::<Vec<String>>()
+            .join(" ");
+        svg.push_str(&format!(
+            "<polyline class=\"route\" points=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"{}\"/>\n",
+            route, stroke
+        ));
+
+        let start = self.trace.first().unwrap().position;
+        let end = self.trace.last().unwrap().position;
+        for (class, (x, y), colour) in &[("start", start, "green"), ("end", end, "red")] {
+            svg.push_str(&format!(
+                "<circle class=\"{}\" cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>\n",
+                class,
+                x,
+                -y,
+                stroke * 4.0,
+                colour
+            ));
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -153,7 +360,7 @@ F7
 R90
 F11";
 
-        assert_eq!(part_1(parse_input(input)), 25)
+        assert_eq!(part_1(parse_input(input)).unwrap(), 25)
     }
 
     #[test]
@@ -165,6 +372,85 @@ R90
 F11
 L180";
 
-        assert_eq!(part_2(parse_input(input)), 286)
+        assert_eq!(part_2(parse_input(input)).unwrap(), 286)
+    }
+
+    #[test]
+    fn test_trace() {
+        let input = parse_input("F10\nN3\nF7\nR90\nF11");
+
+        let navigator = Navigator::run(Mode::Ship, &input).unwrap();
+        assert_eq!(
+            navigator
+                .trace
+                .iter()
+                .map(|s| (s.position, s.heading))
+                .collect::<Vec<((i32, i32), Option<char>)>>(),
+            vec![
+                ((0, 0), Some('E')),
+                ((10, 0), Some('E')),
+                ((10, 3), Some('E')),
+                ((17, 3), Some('E')),
+                ((17, 3), Some('S')),
+                ((17, -8), Some('S'))
+            ]
+        );
+        assert!(navigator.trace.iter().all(|s| s.waypoint.is_none()));
+
+        let navigator = Navigator::run(Mode::Waypoint, &input).unwrap();
+        assert_eq!(
+            navigator.trace[3..],
+            [
+                Step {
+                    instruction: Some(('F', 7)),
+                    position: (170, 38),
+                    heading: None,
+                    waypoint: Some((10, 4))
+                },
+                Step {
+                    instruction: Some(('R', 90)),
+                    position: (170, 38),
+                    heading: None,
+                    waypoint: Some((4, -10))
+                },
+                Step {
+                    instruction: Some(('F', 11)),
+                    position: (214, -72),
+                    heading: None,
+                    waypoint: Some((4, -10))
+                }
+            ]
+        );
+
+        assert!(Navigator::run(Mode::Ship, &[('R', 45)]).is_err());
+        assert!(Navigator::run(Mode::Ship, &[('F', 1), ('X', 1)]).is_err());
+    }
+
+    #[test]
+    fn test_exports() {
+        let input = parse_input("F10\nN3\nF7\nR90\nF11");
+
+        let csv = Navigator::run(Mode::Ship, &input).unwrap().to_csv();
+        assert_eq!(
+            csv.lines().take(3).collect::<Vec<&str>>(),
+            vec![
+                "step,instruction,x,y,heading,waypoint_x,waypoint_y",
+                "0,,0,0,E,,",
+                "1,F10,10,0,E,,"
+            ]
+        );
+        let csv = Navigator::run(Mode::Waypoint, &input).unwrap().to_csv();
+        assert_eq!(csv.lines().last(), Some("5,F11,214,-72,,4,-10"));
+
+        let svg = Navigator::run(Mode::Waypoint, &input).unwrap().to_svg();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains("points=\"0,0 100,-10 100,-10 170,-38 170,-38 214,72\""));
+        assert!(svg.contains("<circle class=\"start\" cx=\"0\" cy=\"0\""));
+        assert!(svg.contains("<circle class=\"end\" cx=\"214\" cy=\"72\""));
+        assert_eq!(svg.matches("<line class=\"waypoint\"").count(), 6);
+        assert!(!Navigator::run(Mode::Ship, &input)
+            .unwrap()
+            .to_svg()
+            .contains("<line"));
     }
 }